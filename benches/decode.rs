@@ -0,0 +1,54 @@
+// This is free and unencumbered software released into the public domain.
+
+use evm_rs::{decode_instructions, decode_program};
+use std::{hint::black_box, time::Instant};
+
+const ROUNDS: usize = 200;
+
+/// Generates 24 KiB of valid bytecode, heavy in PUSHes as compiled
+/// contracts are.
+fn bytecode() -> Vec<u8> {
+    let mut result = Vec::new();
+    let mut seed: u32 = 1;
+    while result.len() < 24 * 1024 {
+        seed = seed.wrapping_mul(1103515245).wrapping_add(12345);
+        match seed >> 28 {
+            0..=5 => {
+                let width = 1 + (seed >> 8) as usize % 32;
+                result.push(0x5F + width as u8);
+                result.extend((0..width).map(|i| (seed >> (i % 24)) as u8));
+            }
+            6..=9 => result.push(0x80 + (seed >> 8) as u8 % 16),
+            10..=12 => result.extend([0x01, 0x16, 0x52]),
+            _ => result.extend([0x5B, 0x56, 0x57, 0x54]),
+        }
+    }
+    result
+}
+
+fn bench(name: &str, input: &[u8], mut decode: impl FnMut(&[u8]) -> usize) {
+    let start = Instant::now();
+    let mut count = 0;
+    for _ in 0..ROUNDS {
+        count += decode(black_box(input));
+    }
+    let elapsed = start.elapsed();
+    let throughput = (input.len() * ROUNDS) as f64 / elapsed.as_secs_f64() / (1 << 20) as f64;
+    println!(
+        "{:<24} {:>10.2?} {:>10.1} MiB/s ({} instructions)",
+        name,
+        elapsed / ROUNDS as u32,
+        throughput,
+        count / ROUNDS
+    );
+}
+
+fn main() {
+    let input = bytecode();
+    bench("decode_program", &input, |input| {
+        decode_program(input).unwrap().0.len()
+    });
+    bench("decode_instructions", &input, |input| {
+        decode_instructions(input).map(Result::unwrap).count()
+    });
+}
@@ -0,0 +1,232 @@
+// This is free and unencumbered software released into the public domain.
+
+use alloc::{boxed::Box, collections::BTreeMap, vec::Vec};
+use core::fmt;
+use ethnum::u256;
+#[cfg(feature = "std")]
+use serde_json::{json, Value};
+
+use crate::{
+    dispatch::entry_state,
+    opcode::Opcode,
+    program::Program,
+    symbolic::{explore_symbolic, Expr, SymbolicLimits, SymbolicState},
+};
+
+const MAX_PARAMS: usize = 32;
+
+#[derive(Clone, Debug, Hash, PartialEq, Eq, PartialOrd, Ord)]
+pub enum AbiType {
+    Uint(u16),
+    Int(u16),
+    Address,
+    Bool,
+    FixedBytes(u8),
+    /// A dynamic byte array, which could also be a `string`.
+    Bytes,
+    Array(Box<AbiType>),
+}
+
+impl fmt::Display for AbiType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        use AbiType::*;
+        match self {
+            Uint(bits) => write!(f, "uint{}", bits),
+            Int(bits) => write!(f, "int{}", bits),
+            Address => write!(f, "address"),
+            Bool => write!(f, "bool"),
+            FixedBytes(size) => write!(f, "bytes{}", size),
+            Bytes => write!(f, "bytes"),
+            Array(element) => write!(f, "{}[]", element),
+        }
+    }
+}
+
+/// Infers the parameter types of the external function starting at `entry`
+/// from how it reads and validates its calldata.
+///
+/// The function is executed symbolically with the selector as the only
+/// stack item, as left by the dispatchers of solc and Vyper. Each head word
+/// at `CALLDATALOAD(4 + 32 * i)` is then typed by the masks, sign
+/// extensions and boolean normalizations applied to it, and as dynamic if it
+/// is used as an offset into calldata. Words read without any of those are
+/// taken to be `uint256`.
+pub fn infer_inputs(program: &Program, entry: usize) -> Vec<AbiType> {
+    let initial = entry_state(entry);
+    let limits = SymbolicLimits {
+        max_steps: 10_000,
+        max_paths: 256,
+    };
+    let mut candidates: BTreeMap<usize, Vec<AbiType>> = BTreeMap::new();
+    let mut lengths: BTreeMap<usize, Expr> = BTreeMap::new();
+    let mut scaled: Vec<Expr> = Vec::new();
+    for state in explore_symbolic(program, initial, limits) {
+        for root in roots(&state) {
+            root.visit(&mut |expr| {
+                if let Some(index) = head(expr) {
+                    candidates.entry(index).or_default();
+                }
+                if let Some((index, candidate)) = cleanup(expr) {
+                    candidates.entry(index).or_default().push(candidate);
+                }
+                if let Some((index, length)) = length(expr) {
+                    lengths.insert(index, length);
+                }
+                if let Expr::Op(Opcode::MUL | Opcode::SHL, _) = expr {
+                    scaled.push(expr.clone());
+                }
+            });
+        }
+    }
+    for (index, length) in lengths {
+        let element = Box::new(AbiType::Uint(256));
+        let candidate = match scaled.iter().any(|expr| is_scaled(expr, &length)) {
+            true => AbiType::Array(element),
+            false => AbiType::Bytes,
+        };
+        candidates.entry(index).or_default().push(candidate);
+    }
+    let count = candidates.keys().next_back().map_or(0, |index| index + 1);
+    (0..count)
+        .map(|index| {
+            candidates
+                .get(&index)
+                .and_then(|candidates| candidates.iter().max_by_key(|c| priority(c)))
+                .cloned()
+                .unwrap_or(AbiType::Uint(256))
+        })
+        .collect()
+}
+
+/// Formats a function as a Solidity ABI JSON fragment.
+#[cfg(feature = "std")]
+pub fn abi_json(name: &str, inputs: &[AbiType], payable: bool) -> String {
+    let inputs: Vec<Value> = inputs
+        .iter()
+        .enumerate()
+        .map(|(i, input)| json!({"name": format!("arg{}", i), "type": input.to_string()}))
+        .collect();
+    json!({
+        "type": "function",
+        "name": name,
+        "inputs": inputs,
+        "outputs": [],
+        "stateMutability": if payable { "payable" } else { "nonpayable" },
+    })
+    .to_string()
+}
+
+fn roots(state: &SymbolicState) -> Vec<&Expr> {
+    let mut result: Vec<&Expr> = Vec::new();
+    result.extend(&state.stack);
+    result.extend(&state.constraints);
+    for write in &state.memory {
+        result.extend([&write.offset, &write.size]);
+        result.extend(&write.value);
+    }
+    for (key, value) in &state.storage {
+        result.extend([key, value]);
+    }
+    for effect in &state.effects {
+        result.extend(&effect.args);
+    }
+    result
+}
+
+/// Returns the parameter index of a head word read.
+fn head(expr: &Expr) -> Option<usize> {
+    let Expr::Op(Opcode::CALLDATALOAD, args) = expr else {
+        return None;
+    };
+    let offset = usize::try_from(args[0].constant()?).ok()?;
+    let index = offset.checked_sub(4)? / 32;
+    (offset % 32 == 4 && index < MAX_PARAMS).then_some(index)
+}
+
+/// Returns the type implied by a cleanup or validation of a head word.
+fn cleanup(expr: &Expr) -> Option<(usize, AbiType)> {
+    let Expr::Op(opcode, args) = expr else {
+        return None;
+    };
+    match (opcode, args.as_slice()) {
+        (Opcode::AND, [Expr::Const(mask), word] | [word, Expr::Const(mask)]) => {
+            Some((head(word)?, mask_type(*mask)?))
+        }
+        (Opcode::SIGNEXTEND, [Expr::Const(size), word]) if *size < 32 => {
+            Some((head(word)?, AbiType::Int(8 * (size.as_u16() + 1))))
+        }
+        (Opcode::ISZERO, [Expr::Op(Opcode::ISZERO, inner)]) => {
+            Some((head(&inner[0])?, AbiType::Bool))
+        }
+        _ => None,
+    }
+}
+
+/// Returns the length read of a dynamic parameter, i.e. a `CALLDATALOAD`
+/// at the offset held in a head word, relative to the start of the
+/// arguments.
+fn length(expr: &Expr) -> Option<(usize, Expr)> {
+    let Expr::Op(Opcode::CALLDATALOAD, args) = expr else {
+        return None;
+    };
+    match &args[0] {
+        Expr::Op(Opcode::ADD, offset) => match offset.as_slice() {
+            [Expr::Const(start), word] | [word, Expr::Const(start)] if *start == 4 => {
+                Some((head(word)?, expr.clone()))
+            }
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+/// Whether an expression scales a length by the word size, as done for
+/// arrays but not for byte arrays.
+fn is_scaled(expr: &Expr, length: &Expr) -> bool {
+    match expr {
+        Expr::Op(Opcode::MUL, args) => {
+            args.contains(&Expr::Const(u256::new(32))) && args.contains(length)
+        }
+        Expr::Op(Opcode::SHL, args) => args[0] == Expr::Const(u256::new(5)) && args[1] == *length,
+        _ => false,
+    }
+}
+
+fn priority(candidate: &AbiType) -> u8 {
+    match candidate {
+        AbiType::Bytes | AbiType::Array(_) => 3,
+        AbiType::Bool => 2,
+        AbiType::Uint(256) => 0,
+        _ => 1,
+    }
+}
+
+/// Returns the type whose cleanup is an AND with this mask.
+fn mask_type(mask: u256) -> Option<AbiType> {
+    let (ones, zeros) = (mask.count_ones(), mask.trailing_zeros());
+    if ones == 0 || ones % 8 != 0 || mask.leading_zeros() + ones + zeros != 256 {
+        return None;
+    }
+    match (zeros, ones) {
+        (0, 160) => Some(AbiType::Address),
+        (0, 256) => None,
+        (0, bits) => Some(AbiType::Uint(bits as u16)),
+        (_, bits) if zeros + ones == 256 => Some(AbiType::FixedBytes((bits / 8) as u8)),
+        _ => None,
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn abi_json_escapes_name() {
+        let name = "f\"\\\n\u{1}";
+        let json = abi_json(name, &[AbiType::Address, AbiType::Uint(8)], false);
+        let value: Value = serde_json::from_str(&json).unwrap();
+        assert_eq!(value["name"], name);
+        assert_eq!(value["inputs"][1], json!({"name": "arg1", "type": "uint8"}));
+        assert_eq!(value["stateMutability"], "nonpayable");
+    }
+}
@@ -0,0 +1,167 @@
+// This is free and unencumbered software released into the public domain.
+
+use alloc::{collections::BTreeMap, vec::Vec};
+use ethnum::u256;
+#[cfg(feature = "std")]
+use std::{fs, io, path::Path};
+
+#[cfg(feature = "std")]
+use crate::parse::parse_program_with;
+use crate::{
+    encode::encode_program,
+    error::ParseError,
+    opcode::Opcode,
+    parse::{parse_program, AsmStatement},
+    program::Program,
+};
+
+/// Assembles assembly text into bytecode.
+pub fn assemble(input: &str) -> Result<Vec<u8>, ParseError> {
+    Ok(encode_program(parse_program(input)?))
+}
+
+/// Assembles a file into bytecode, reading each included file relative to
+/// the directory of the file including it.
+#[cfg(feature = "std")]
+pub fn assemble_file(path: impl AsRef<Path>) -> io::Result<Vec<u8>> {
+    let path = path.as_ref();
+    let input = fs::read_to_string(path)?;
+    let mut include = |from: &str, name: &str| {
+        let from = if from.is_empty() {
+            path
+        } else {
+            Path::new(from)
+        };
+        let path = from.parent().unwrap_or(Path::new("")).join(name);
+        let text = fs::read_to_string(&path).ok()?;
+        Some((path.to_str()?.to_string(), text))
+    };
+    let program = parse_program_with(&input, &mut include)
+        .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err.to_string()))?;
+    Ok(encode_program(program))
+}
+
+/// Resolves the labels pushed by statements to the positions they mark.
+///
+/// Label PUSHes without a width start out at one byte and are widened
+/// until every label's position fits, as widening a PUSH moves the labels
+/// after it.
+pub fn assemble_statements(statements: &[AsmStatement]) -> Result<Program, ParseError> {
+    let mut widths: Vec<u8> = statements
+        .iter()
+        .map(|stmt| match stmt {
+            AsmStatement::PushLabel { width, .. } => width.unwrap_or(1),
+            _ => 0,
+        })
+        .collect();
+    let labels = loop {
+        let labels = positions(statements, &widths);
+        let mut changed = false;
+        for (stmt, width) in statements.iter().zip(&mut widths) {
+            let AsmStatement::PushLabel {
+                width: None,
+                label,
+                line,
+                column,
+            } = stmt
+            else {
+                continue;
+            };
+            let position = *labels
+                .get(label.as_str())
+                .ok_or(ParseError::UndefinedLabel(*line, *column))?;
+            while Opcode::push_sized(*width, position).is_err() {
+                *width += 1;
+                changed = true;
+            }
+        }
+        if !changed {
+            break labels;
+        }
+    };
+    let mut result = Vec::new();
+    for (stmt, &width) in statements.iter().zip(&widths) {
+        match stmt {
+            AsmStatement::Label(_) => {}
+            AsmStatement::Opcode(op) => result.push(op.clone()),
+            AsmStatement::PushLabel {
+                label,
+                line,
+                column,
+                ..
+            } => {
+                let position = *labels
+                    .get(label.as_str())
+                    .ok_or(ParseError::UndefinedLabel(*line, *column))?;
+                let op = Opcode::push_sized(width, position)
+                    .map_err(|_| ParseError::LabelOutOfRange(*line, *column))?;
+                result.push(op);
+            }
+        }
+    }
+    Ok(Program(result))
+}
+
+/// Returns the position of each label given the widths of label PUSHes.
+fn positions<'a>(statements: &'a [AsmStatement], widths: &[u8]) -> BTreeMap<&'a str, u256> {
+    let mut result = BTreeMap::new();
+    let mut pc = 0;
+    for (stmt, &width) in statements.iter().zip(widths) {
+        match stmt {
+            AsmStatement::Label(label) => {
+                result.insert(label.as_str(), u256::from(pc as u64));
+            }
+            AsmStatement::Opcode(op) => pc += op.size(),
+            AsmStatement::PushLabel { .. } => pc += 1 + width as usize,
+        }
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::{format, string::String};
+
+    fn padded(nops: usize) -> String {
+        format!("PUSH @end\n{}end:\nJUMPDEST", "JUMPDEST\n".repeat(nops))
+    }
+
+    #[test]
+    fn label_widening() {
+        let program = parse_program(&padded(253)).unwrap();
+        assert_eq!(program.0[0], Opcode::push_sized(1, u256::new(255)).unwrap());
+        // Widening the PUSH moves the label past 255 bytes.
+        let program = parse_program(&padded(254)).unwrap();
+        assert_eq!(program.0[0], Opcode::push_sized(2, u256::new(257)).unwrap());
+        assert_eq!(assemble(&padded(254)).unwrap()[..3], [0x61, 0x01, 0x01]);
+    }
+
+    #[test]
+    fn label_out_of_range() {
+        let input = format!("PUSH1 @end\n{}end:", "JUMPDEST\n".repeat(254));
+        assert_eq!(
+            parse_program(&input),
+            Err(ParseError::LabelOutOfRange(1, 7))
+        );
+        let input = input.replacen("PUSH1", "PUSH2", 1);
+        assert_eq!(assemble(&input).unwrap()[..3], [0x61, 0x01, 0x01]);
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn includes_relative_to_including_file() {
+        let root = std::env::temp_dir().join(format!("evm_rs_include_{}", std::process::id()));
+        fs::create_dir_all(root.join("lib")).unwrap();
+        fs::write(root.join("main.asm"), "%include \"lib/a.asm\"\nSTOP").unwrap();
+        fs::write(root.join("lib/a.asm"), "%include \"b.asm\"\nPUSH1 ONE").unwrap();
+        fs::write(root.join("lib/b.asm"), "%define ONE 1\nPUSH1 2").unwrap();
+        fs::write(root.join("b.asm"), "INVALID").unwrap();
+        fs::write(root.join("missing.asm"), "%include \"lib/c.asm\"").unwrap();
+        let bytes = assemble_file(root.join("main.asm"));
+        let missing = assemble_file(root.join("missing.asm"));
+        fs::remove_dir_all(&root).unwrap();
+        assert_eq!(bytes.unwrap(), [0x60, 0x02, 0x60, 0x01, 0x00]);
+        assert_eq!(missing.unwrap_err().kind(), io::ErrorKind::InvalidData);
+    }
+}
@@ -0,0 +1,72 @@
+// This is free and unencumbered software released into the public domain.
+
+use alloc::vec::Vec;
+
+use crate::opcode::Opcode;
+
+#[derive(Clone, Debug, Hash, PartialEq, Eq, PartialOrd, Ord)]
+pub struct BasicBlock {
+    pub start: usize,
+    pub opcodes: Vec<Opcode>,
+}
+
+impl BasicBlock {
+    pub fn new(start: usize) -> Self {
+        BasicBlock {
+            start,
+            opcodes: Vec::new(),
+        }
+    }
+
+    pub fn end(&self) -> usize {
+        self.start + self.opcodes.iter().map(Opcode::size).sum::<usize>()
+    }
+
+    pub fn falls_through(&self) -> bool {
+        use Opcode::*;
+        !matches!(
+            self.opcodes.last(),
+            Some(STOP | JUMP | RETURN | REVERT | INVALID | SELFDESTRUCT)
+        )
+    }
+
+    pub fn instructions(&self) -> impl Iterator<Item = (usize, &Opcode)> {
+        self.opcodes.iter().scan(self.start, |pc, op| {
+            let result = (*pc, op);
+            *pc += op.size();
+            Some(result)
+        })
+    }
+
+    pub fn is_jumpdest(&self) -> bool {
+        self.opcodes.first().is_some_and(Opcode::is_jumpdest)
+    }
+
+    pub fn terminator(&self) -> Option<&Opcode> {
+        self.opcodes.last().filter(|op| op.is_control())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse::parse_program;
+
+    #[test]
+    fn splitting() {
+        let program =
+            parse_program("PUSH 1 PUSH @a JUMPI PUSH 2 a: JUMPDEST JUMPDEST STOP INVALID").unwrap();
+        let blocks = program.basic_blocks();
+        let bounds: Vec<(usize, usize)> = blocks.iter().map(|b| (b.start, b.end())).collect();
+        assert_eq!(bounds, [(0, 5), (5, 7), (7, 8), (8, 10), (10, 11)]);
+        let falls: Vec<bool> = blocks.iter().map(BasicBlock::falls_through).collect();
+        assert_eq!(falls, [true, true, true, false, false]);
+        let jumpdests: Vec<bool> = blocks.iter().map(BasicBlock::is_jumpdest).collect();
+        assert_eq!(jumpdests, [false, false, true, true, false]);
+        assert_eq!(blocks[0].terminator(), Some(&Opcode::JUMPI));
+        assert_eq!(blocks[1].terminator(), None);
+        assert_eq!(blocks[3].terminator(), Some(&Opcode::STOP));
+        let pcs: Vec<usize> = blocks[3].instructions().map(|(pc, _)| pc).collect();
+        assert_eq!(pcs, [8, 9]);
+    }
+}
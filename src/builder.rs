@@ -0,0 +1,210 @@
+// This is free and unencumbered software released into the public domain.
+
+use alloc::{
+    collections::BTreeSet,
+    format,
+    string::{String, ToString},
+    vec::Vec,
+};
+use ethnum::u256;
+
+use crate::{
+    assemble::assemble_statements,
+    error::{BuildError, ParseError},
+    opcode::Opcode,
+    parse::{is_label, AsmStatement},
+    program::Program,
+};
+
+/// Builds a program instruction by instruction, resolving labels and
+/// sizing PUSHes when it is built.
+#[derive(Clone, Debug, Default)]
+pub struct ProgramBuilder {
+    statements: Vec<AsmStatement>,
+    labels: BTreeSet<String>,
+    /// The first invalid or duplicate label, reported when built.
+    error: Option<BuildError>,
+    returns: usize,
+}
+
+impl ProgramBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn op(&mut self, op: Opcode) -> &mut Self {
+        self.statements.push(AsmStatement::Opcode(op));
+        self
+    }
+
+    /// Pushes a value with the smallest PUSH that fits it, at least a
+    /// `PUSH1`, so that zero is pushed without `PUSH0` for chains that
+    /// predate Shanghai.
+    pub fn push(&mut self, value: impl Into<u256>) -> &mut Self {
+        self.op(Opcode::push(value.into()))
+    }
+
+    /// Pushes the position of a label, with the smallest PUSH that fits it.
+    pub fn push_label(&mut self, label: &str) -> &mut Self {
+        self.check(label);
+        self.reference(label)
+    }
+
+    /// Defines a label at a new `JUMPDEST`.
+    pub fn label(&mut self, label: &str) -> &mut Self {
+        self.check(label);
+        if !self.labels.insert(label.to_string()) && self.error.is_none() {
+            self.error = Some(BuildError::DuplicateLabel(label.to_string()));
+        }
+        self.statements.push(AsmStatement::Label(label.to_string()));
+        self.op(Opcode::JUMPDEST)
+    }
+
+    pub fn jump_to(&mut self, label: &str) -> &mut Self {
+        self.push_label(label).op(Opcode::JUMP)
+    }
+
+    /// Jumps to a label if the top of the stack is nonzero.
+    pub fn jumpi_to(&mut self, label: &str) -> &mut Self {
+        self.push_label(label).op(Opcode::JUMPI)
+    }
+
+    /// Pushes the arguments of an instruction, in the order they appear in
+    /// its functional form such as `MSTORE(offset, value)`, and appends it.
+    pub fn apply(&mut self, op: Opcode, args: impl IntoIterator<Item = u256>) -> &mut Self {
+        let args: Vec<u256> = args.into_iter().collect();
+        for arg in args.into_iter().rev() {
+            self.push(arg);
+        }
+        self.op(op)
+    }
+
+    /// Calls the internal function at a label, pushing the return address
+    /// on top of any arguments already on the stack.
+    ///
+    /// The callee finds the address on top of the stack on entry, unlike in
+    /// Solidity's convention where it is below the arguments, and returns by
+    /// bringing it back to the top, e.g. with a `SWAP`, and jumping to it.
+    pub fn call(&mut self, label: &str) -> &mut Self {
+        self.returns += 1;
+        // The return address is a fresh label, which no user label can
+        // collide with as it is not a valid label.
+        let ret = format!("return#{}", self.returns);
+        self.reference(&ret).jump_to(label);
+        self.labels.insert(ret.clone());
+        self.statements.push(AsmStatement::Label(ret));
+        self.op(Opcode::JUMPDEST)
+    }
+
+    /// Appends a `DUP` of the `N`th stack item, checking `N` at compile time.
+    pub fn dup<const N: u8>(&mut self) -> &mut Self {
+        const { assert!(N >= 1 && N <= 16, "DUP depth must be between 1 and 16") };
+        self.op(Opcode::DUP(N))
+    }
+
+    /// Appends a `SWAP` of the top and the `N + 1`th stack items, checking
+    /// `N` at compile time.
+    pub fn swap<const N: u8>(&mut self) -> &mut Self {
+        const { assert!(N >= 1 && N <= 16, "SWAP depth must be between 1 and 16") };
+        self.op(Opcode::SWAP(N))
+    }
+
+    fn reference(&mut self, label: &str) -> &mut Self {
+        self.statements.push(AsmStatement::PushLabel {
+            width: None,
+            label: label.to_string(),
+            line: 0,
+            column: 0,
+        });
+        self
+    }
+
+    /// Records the first label that is not valid assembly syntax, which also
+    /// keeps user labels apart from the return labels of calls.
+    fn check(&mut self, label: &str) {
+        if !is_label(label) && self.error.is_none() {
+            self.error = Some(BuildError::InvalidLabel(label.to_string()));
+        }
+    }
+
+    /// Builds the program, resolving its labels.
+    pub fn build(&self) -> Result<Program, BuildError> {
+        if let Some(err) = &self.error {
+            return Err(err.clone());
+        }
+        assemble_statements(&self.statements).map_err(|err| {
+            let label = self.statements.iter().find_map(|stmt| match stmt {
+                AsmStatement::PushLabel { label, .. } if !self.labels.contains(label) => {
+                    Some(label.clone())
+                }
+                _ => None,
+            });
+            match (err, label) {
+                (ParseError::UndefinedLabel(..), Some(label)) => BuildError::UndefinedLabel(label),
+                (err, _) => unreachable!("unexpected assembly error: {}", err),
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::encode::encode_program;
+    use alloc::vec;
+
+    #[test]
+    fn push_widths() {
+        let program = ProgramBuilder::new()
+            .push(0u8)
+            .push(0x100u16)
+            .push(u256::MAX)
+            .build()
+            .unwrap();
+        let widths: Vec<usize> = program.0.iter().map(|op| op.size() - 1).collect();
+        assert_eq!(widths, vec![1, 2, 32]);
+    }
+
+    #[test]
+    fn label_widening() {
+        let mut builder = ProgramBuilder::new();
+        builder.jump_to("end");
+        for _ in 0..300 {
+            builder.op(Opcode::STOP);
+        }
+        let program = builder.label("end").build().unwrap();
+        // PUSH2, JUMP and the STOPs come before the label.
+        assert_eq!(program.0[0], Opcode::push_sized(2, u256::new(304)).unwrap());
+    }
+
+    #[test]
+    fn call_and_return() {
+        let program = ProgramBuilder::new()
+            .call("f")
+            .op(Opcode::STOP)
+            .label("f")
+            .op(Opcode::JUMP)
+            .build()
+            .unwrap();
+        let expected = [0x60, 0x05, 0x60, 0x07, 0x56, 0x5b, 0x00, 0x5b, 0x56];
+        assert_eq!(encode_program(program), expected);
+    }
+
+    #[test]
+    fn label_errors() {
+        let result = ProgramBuilder::new().label("a").label("a").build();
+        assert_eq!(result, Err(BuildError::DuplicateLabel("a".to_string())));
+        let result = ProgramBuilder::new().jump_to("missing").build();
+        assert_eq!(
+            result,
+            Err(BuildError::UndefinedLabel("missing".to_string()))
+        );
+        let result = ProgramBuilder::new().label("return#1").call("f").build();
+        assert_eq!(
+            result,
+            Err(BuildError::InvalidLabel("return#1".to_string()))
+        );
+        let result = ProgramBuilder::new().push_label("1a").label("f").build();
+        assert_eq!(result, Err(BuildError::InvalidLabel("1a".to_string())));
+    }
+}
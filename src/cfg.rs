@@ -0,0 +1,185 @@
+// This is free and unencumbered software released into the public domain.
+
+use alloc::{collections::BTreeSet, vec, vec::Vec};
+use ethnum::u256;
+
+use crate::{
+    block::BasicBlock,
+    lift::{lift_block, LiftedBlock, Value},
+    opcode::Opcode,
+    program::Program,
+};
+
+const MAX_DEPTH: usize = 1024;
+const MAX_TARGETS: usize = 64;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Cfg {
+    pub blocks: Vec<BasicBlock>,
+    pub lifted: Vec<LiftedBlock>,
+    pub successors: Vec<BTreeSet<usize>>,
+    pub predecessors: Vec<BTreeSet<usize>>,
+    /// How many entry stack items each block and its successors read.
+    pub depths: Vec<usize>,
+}
+
+/// Builds the control-flow graph of a program, resolving jumps whose targets
+/// are constants pushed by a predecessor (e.g. internal function returns).
+pub fn build_cfg(program: &Program) -> Cfg {
+    let blocks = program.basic_blocks();
+    let mut next_var = 0;
+    let lifted: Vec<LiftedBlock> = blocks
+        .iter()
+        .map(|block| lift_block(block, &mut next_var))
+        .collect();
+    let n = blocks.len();
+    let mut cfg = Cfg {
+        depths: lifted.iter().map(|block| block.inputs).collect(),
+        blocks,
+        lifted,
+        successors: vec![BTreeSet::new(); n],
+        predecessors: vec![BTreeSet::new(); n],
+    };
+    for b in 0..n {
+        let terminator = cfg.lifted[b].terminator().cloned();
+        let (falls_through, target) = match &terminator {
+            Some(stmt) if stmt.opcode == Opcode::JUMP => (false, Some(stmt.args[0])),
+            Some(stmt) if stmt.opcode == Opcode::JUMPI => match stmt.args[1].constant() {
+                Some(u256::ZERO) => (true, None),
+                Some(_) => (false, Some(stmt.args[0])),
+                None => (true, Some(stmt.args[0])),
+            },
+            Some(_) => (false, None),
+            None => (true, None),
+        };
+        if falls_through && b + 1 < n {
+            cfg.add_edge(b, b + 1);
+        }
+        if let Some(target) = target
+            .and_then(|v| v.constant())
+            .and_then(|c| cfg.jump_target(c))
+        {
+            cfg.add_edge(b, target);
+        }
+    }
+    loop {
+        cfg.update_depths();
+        let values = cfg.entry_values();
+        let mut changed = false;
+        for (b, values) in values.iter().enumerate() {
+            let target = match cfg.lifted[b].terminator() {
+                Some(stmt) if stmt.opcode.is_jump() => stmt.args[0],
+                _ => continue,
+            };
+            let constants = match target {
+                Value::Input(k) => values.get(k).cloned().flatten().unwrap_or_default(),
+                _ => continue,
+            };
+            for constant in constants {
+                if let Some(target) = cfg.jump_target(constant) {
+                    changed |= cfg.add_edge(b, target);
+                }
+            }
+        }
+        if !changed {
+            break;
+        }
+    }
+    cfg
+}
+
+impl Cfg {
+    pub fn block_at(&self, pc: usize) -> Option<usize> {
+        self.blocks
+            .binary_search_by_key(&pc, |block| block.start)
+            .ok()
+    }
+
+    /// Returns the exit stack item at this depth, in terms of the block's
+    /// entry stack.
+    pub fn exit(&self, block: usize, slot: usize) -> Value {
+        let lifted = &self.lifted[block];
+        match lifted.outputs.get(slot) {
+            Some(value) => *value,
+            None => Value::Input(slot - lifted.outputs.len() + lifted.inputs),
+        }
+    }
+
+    pub fn jump_target(&self, pc: u256) -> Option<usize> {
+        let pc = usize::try_from(pc).ok()?;
+        self.block_at(pc)
+            .filter(|&block| self.blocks[block].is_jumpdest())
+    }
+
+    pub fn reachable(&self) -> Vec<bool> {
+        let mut result = vec![false; self.blocks.len()];
+        let mut pending = vec![0];
+        while let Some(block) = pending.pop() {
+            if block < result.len() && !result[block] {
+                result[block] = true;
+                pending.extend(self.successors[block].iter().copied());
+            }
+        }
+        result
+    }
+
+    fn add_edge(&mut self, from: usize, to: usize) -> bool {
+        self.predecessors[to].insert(from);
+        self.successors[from].insert(to)
+    }
+
+    fn update_depths(&mut self) {
+        let mut changed = true;
+        while changed {
+            changed = false;
+            for b in 0..self.blocks.len() {
+                let lifted = &self.lifted[b];
+                for &s in &self.successors[b] {
+                    let depth = (self.depths[s] + lifted.inputs)
+                        .saturating_sub(lifted.outputs.len())
+                        .min(MAX_DEPTH);
+                    if depth > self.depths[b] {
+                        self.depths[b] = depth;
+                        changed = true;
+                    }
+                }
+            }
+        }
+    }
+
+    /// Computes the constants each entry stack item may hold, with `None`
+    /// standing for values that are not (or not only) constants.
+    fn entry_values(&self) -> Vec<Vec<Option<BTreeSet<u256>>>> {
+        let reachable = self.reachable();
+        let mut values: Vec<Vec<Option<BTreeSet<u256>>>> = self
+            .depths
+            .iter()
+            .map(|&depth| vec![Some(BTreeSet::new()); depth])
+            .collect();
+        let mut changed = true;
+        while changed {
+            changed = false;
+            for b in (0..self.blocks.len()).filter(|&b| reachable[b]) {
+                for &s in &self.successors[b] {
+                    for slot in 0..self.depths[s] {
+                        let exit = match self.exit(b, slot) {
+                            Value::Const(c) => Some(BTreeSet::from([c])),
+                            Value::Input(k) => values[b].get(k).cloned().unwrap_or(None),
+                            Value::Var(_) => None,
+                        };
+                        let joined = match (&values[s][slot], exit) {
+                            (Some(a), Some(b)) => Some(a.union(&b).copied().collect())
+                                .filter(|set: &BTreeSet<u256>| set.len() <= MAX_TARGETS),
+                            _ => None,
+                        };
+                        if joined != values[s][slot] {
+                            values[s][slot] = joined;
+                            changed = true;
+                        }
+                    }
+                }
+            }
+        }
+        values
+    }
+}
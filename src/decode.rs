@@ -1,8 +1,12 @@
 // This is free and unencumbered software released into the public domain.
 
-use ethnum::u256;
+use alloc::vec::Vec;
 
-use crate::{error::DecodeError, opcode::Opcode, program::Program};
+use crate::{
+    error::DecodeError,
+    opcode::{Opcode, Push},
+    program::Program,
+};
 
 pub fn decode_program(input: &[u8]) -> Result<Program, DecodeError> {
     Ok(Program(decode_opcodes(input)?))
@@ -23,6 +27,91 @@ pub fn decode_opcodes(input: &[u8]) -> Result<Vec<Opcode>, DecodeError> {
     Ok(result)
 }
 
+/// Decodes the instructions of bytecode lazily, borrowing their immediates
+/// from the input. Decoding stops after the first error.
+pub fn decode_instructions(input: &[u8]) -> Instructions<'_> {
+    Instructions { input, pc: 0 }
+}
+
+/// Whether each byte is an opcode that [`decode_opcode`] accepts.
+const VALID: [bool; 256] = {
+    let invalid = [
+        (0x0C, 0x0F),
+        (0x1E, 0x1F),
+        (0x21, 0x2F),
+        (0x49, 0x4F),
+        (0x5C, 0x5E),
+        (0xA5, 0xEF),
+        (0xF6, 0xF9),
+        (0xFB, 0xFC),
+    ];
+    let mut result = [true; 256];
+    let mut i = 0;
+    while i < invalid.len() {
+        let mut byte = invalid[i].0;
+        while byte <= invalid[i].1 {
+            result[byte] = false;
+            byte += 1;
+        }
+        i += 1;
+    }
+    result
+};
+
+/// An instruction borrowed from bytecode.
+#[derive(Clone, Copy, Debug, Hash, PartialEq, Eq)]
+pub struct InstructionRef<'a> {
+    pub pc: usize,
+    pub opcode: u8,
+    /// The immediate bytes of a PUSH, otherwise empty.
+    pub immediate: &'a [u8],
+}
+
+impl InstructionRef<'_> {
+    pub fn size(&self) -> usize {
+        1 + self.immediate.len()
+    }
+
+    pub fn to_opcode(&self) -> Opcode {
+        match self.opcode {
+            0x5F..=0x7F => Opcode::PUSH(Push::from_bytes(self.immediate).unwrap()),
+            opcode => decode_opcode(&[opcode]).unwrap(),
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Instructions<'a> {
+    input: &'a [u8],
+    pc: usize,
+}
+
+impl<'a> Iterator for Instructions<'a> {
+    type Item = Result<InstructionRef<'a>, DecodeError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let opcode = *self.input.get(self.pc)?;
+        let size = match opcode {
+            0x5F..=0x7F => (opcode - 0x5F) as usize,
+            _ => 0,
+        };
+        let result = match self.input.get(self.pc + 1..self.pc + 1 + size) {
+            None => Err(DecodeError::InvalidBytecode),
+            Some(_) if !VALID[opcode as usize] => Err(DecodeError::InvalidOpcode(opcode)),
+            Some(immediate) => Ok(InstructionRef {
+                pc: self.pc,
+                opcode,
+                immediate,
+            }),
+        };
+        self.pc = match result {
+            Ok(_) => self.pc + 1 + size,
+            Err(_) => self.input.len(),
+        };
+        Some(result)
+    }
+}
+
 pub fn decode_opcode(input: &[u8]) -> Result<Opcode, DecodeError> {
     use Opcode::*;
     let opcode = input[0];
@@ -96,17 +185,11 @@ pub fn decode_opcode(input: &[u8]) -> Result<Opcode, DecodeError> {
         0x59 => MSIZE,
         0x5A => GAS,
         0x5B => JUMPDEST,
-        0x5C..=0x5F => return invalid,
-        0x60 => PUSH1(input[1]),
-        0x61..=0x7F => {
-            let n = opcode - 0x60 + 1;
-            let mut buffer: [u8; 32] = [0; 32];
-            buffer[(32 - (n as usize))..32].copy_from_slice(&input[1..=n.into()]);
-            PUSHn(
-                n,
-                u256::from_be_bytes(buffer),
-                buffer[(32 - (n as usize))..32].to_vec(),
-            )
+        0x5C..=0x5E => return invalid,
+        0x5F..=0x7F => {
+            let n = (opcode - 0x5F) as usize;
+            let bytes = input.get(1..=n).ok_or(DecodeError::InvalidBytecode)?;
+            PUSH(Push::from_bytes(bytes).unwrap())
         }
         0x80..=0x8F => DUP(opcode - 0x80 + 1),
         0x90..=0x9F => SWAP(opcode - 0x90 + 1),
@@ -128,3 +211,39 @@ pub fn decode_opcode(input: &[u8]) -> Result<Opcode, DecodeError> {
     };
     Ok(result)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::encode::encode_opcodes;
+    use alloc::vec;
+
+    #[test]
+    fn push_round_trip() {
+        for opcode in 0x5F..=0x7Fu8 {
+            let width = (opcode - 0x5F) as usize;
+            let mut bytecode = vec![opcode];
+            bytecode.extend((1..=width as u8).map(|i| i * 7));
+            let opcodes = decode_opcodes(&bytecode).unwrap();
+            let [Opcode::PUSH(push)] = opcodes.as_slice() else {
+                panic!("expected a single PUSH for 0x{:02X}", opcode);
+            };
+            assert_eq!(push.width() as usize, width);
+            assert_eq!(push.bytes(), &bytecode[1..]);
+            assert_eq!(encode_opcodes(&opcodes), bytecode);
+            let instructions: Vec<_> = decode_instructions(&bytecode)
+                .collect::<Result<_, _>>()
+                .unwrap();
+            assert_eq!(instructions[0].to_opcode(), opcodes[0]);
+        }
+    }
+
+    #[test]
+    fn truncated_push() {
+        assert_eq!(
+            decode_opcodes(&[0x61, 0x01]),
+            Err(DecodeError::InvalidBytecode)
+        );
+        assert_eq!(decode_opcodes(&[0x7F]), Err(DecodeError::InvalidBytecode));
+    }
+}
@@ -0,0 +1,1032 @@
+// This is free and unencumbered software released into the public domain.
+
+use alloc::{
+    collections::{BTreeMap, BTreeSet},
+    format,
+    string::{String, ToString},
+    vec,
+    vec::Vec,
+};
+use ethnum::u256;
+
+use crate::{
+    opcode::Opcode,
+    program::Program,
+    ssa::{build_ssa, SsaProgram, SsaStatement, SsaValue},
+};
+
+const MAX_CALLS: usize = 8;
+const MAX_LINES: usize = 20_000;
+
+/// Decompiles a program into Solidity-like pseudocode.
+pub fn decompile_program(program: &Program) -> String {
+    let ssa = build_ssa(program);
+    Decompiler::new(&ssa).decompile()
+}
+
+enum Node {
+    Line(String),
+    /// A condition and its negation, with both branches.
+    If(String, String, Vec<Node>, Vec<Node>),
+    Loop(Vec<Node>),
+    Break,
+    Continue,
+    Label(usize),
+    Goto(usize),
+}
+
+#[derive(Default)]
+struct Context {
+    /// The blocks on the path currently being emitted.
+    path: Vec<usize>,
+    /// The block at which the current region ends.
+    stop: Option<usize>,
+    /// Whether the current region has reached `stop`.
+    reached: bool,
+    /// The enclosing loops, as header and exit blocks.
+    loops: Vec<(usize, Option<usize>)>,
+    /// Code addresses known to be held by phi variables along the path.
+    env: BTreeMap<usize, SsaValue>,
+}
+
+struct Decompiler<'a> {
+    ssa: &'a SsaProgram,
+    succs: Vec<Vec<usize>>,
+    preds: Vec<Vec<usize>>,
+    idom: Vec<Option<usize>>,
+    ipdom: Vec<Option<usize>>,
+    defs: BTreeMap<usize, (usize, usize)>,
+    uses: BTreeMap<usize, Vec<(usize, usize)>>,
+    phi_uses: BTreeSet<usize>,
+    live: BTreeSet<usize>,
+    reverts: Vec<bool>,
+    /// Blocks on a cycle through an internal function return, which are
+    /// emitted once per call rather than as loops.
+    reentrant: Vec<bool>,
+    functions: BTreeMap<usize, u32>,
+    lines: usize,
+}
+
+impl<'a> Decompiler<'a> {
+    fn new(ssa: &'a SsaProgram) -> Self {
+        let n = ssa.blocks.len();
+        let index = |pc: &usize| ssa.blocks.binary_search_by_key(pc, |b| b.start).ok();
+        let succs: Vec<Vec<usize>> = ssa
+            .blocks
+            .iter()
+            .map(|block| block.successors.iter().filter_map(index).collect())
+            .collect();
+        let preds: Vec<Vec<usize>> = ssa
+            .blocks
+            .iter()
+            .map(|block| block.predecessors.iter().filter_map(index).collect())
+            .collect();
+        let idom = match n {
+            0 => Vec::new(),
+            _ => immediate_dominators(0, &succs, &preds),
+        };
+        let ipdom = {
+            let exits: Vec<usize> = (0..n).filter(|&b| succs[b].is_empty()).collect();
+            let mut rsuccs = preds.clone();
+            rsuccs.push(exits.clone());
+            let mut rpreds = succs.clone();
+            for &b in &exits {
+                rpreds[b].push(n);
+            }
+            rpreds.push(Vec::new());
+            let mut ipdom = immediate_dominators(n, &rsuccs, &rpreds);
+            ipdom.truncate(n);
+            ipdom.into_iter().map(|b| b.filter(|&b| b < n)).collect()
+        };
+        let mut decompiler = Decompiler {
+            ssa,
+            succs,
+            preds,
+            idom,
+            ipdom,
+            defs: BTreeMap::new(),
+            uses: BTreeMap::new(),
+            phi_uses: BTreeSet::new(),
+            live: BTreeSet::new(),
+            reverts: Vec::new(),
+            reentrant: vec![false; n],
+            functions: BTreeMap::new(),
+            lines: 0,
+        };
+        decompiler.analyze();
+        decompiler
+    }
+
+    fn analyze(&mut self) {
+        let ssa = self.ssa;
+        let mut used = BTreeSet::new();
+        for (b, block) in ssa.blocks.iter().enumerate() {
+            for (i, stmt) in block.statements.iter().enumerate() {
+                if let Some(var) = stmt.result {
+                    self.defs.insert(var, (b, i));
+                }
+                let skip = usize::from(stmt.opcode.is_jump());
+                for arg in &stmt.args[skip..] {
+                    if let SsaValue::Var(var) = arg {
+                        self.uses.entry(*var).or_default().push((b, i));
+                        used.insert(*var);
+                    }
+                }
+            }
+        }
+        let phis: BTreeMap<usize, Vec<SsaValue>> = ssa
+            .blocks
+            .iter()
+            .flat_map(|block| {
+                block.phis.iter().map(move |phi| {
+                    let args = ssa.phi_args(block, phi).into_iter().map(|(_, v)| v);
+                    (phi.result, args.collect())
+                })
+            })
+            .collect();
+        for args in phis.values() {
+            for arg in args {
+                if let SsaValue::Var(var) = arg {
+                    self.phi_uses.insert(*var);
+                }
+            }
+        }
+        let mut pending: Vec<usize> = phis.keys().filter(|v| used.contains(v)).copied().collect();
+        while let Some(var) = pending.pop() {
+            if !self.live.insert(var) {
+                continue;
+            }
+            for arg in &phis[&var] {
+                if let SsaValue::Var(arg) = arg {
+                    if phis.contains_key(arg) {
+                        pending.push(*arg);
+                    }
+                }
+            }
+        }
+        self.reverts = ssa
+            .blocks
+            .iter()
+            .map(|block| {
+                matches!(
+                    block.terminator().map(|stmt| &stmt.opcode),
+                    Some(Opcode::REVERT | Opcode::INVALID)
+                ) && block.statements.iter().all(|stmt| {
+                    is_pure(&stmt.opcode)
+                        || is_read(&stmt.opcode)
+                        || matches!(
+                            stmt.opcode,
+                            Opcode::MSTORE | Opcode::REVERT | Opcode::INVALID
+                        )
+                })
+            })
+            .collect();
+        for header in 0..ssa.blocks.len() {
+            let body = self.loop_body(header);
+            let returns = body.iter().any(|&b| {
+                ssa.blocks[b].terminator().is_some_and(|stmt| {
+                    stmt.opcode.is_jump() && matches!(stmt.args[0], SsaValue::Var(_))
+                })
+            });
+            if body.len() > 1 && returns {
+                for b in body {
+                    self.reentrant[b] = true;
+                }
+            }
+        }
+        for (b, block) in ssa.blocks.iter().enumerate() {
+            let Some(stmt) = block.terminator().filter(|s| s.opcode == Opcode::JUMPI) else {
+                continue;
+            };
+            let (Some(target), Some(selector)) =
+                (self.target(b, &stmt.args[0]), self.dispatch(&stmt.args[1]))
+            else {
+                continue;
+            };
+            self.functions.insert(target, selector);
+        }
+    }
+
+    fn decompile(&mut self) -> String {
+        let mut out = String::from("contract Decompiled {\n");
+        if !self.ssa.blocks.is_empty() {
+            let body = self.function(0);
+            out.push_str("    function __entry__() public {\n");
+            print(&body, 2, &gotos(&body), &mut out);
+            out.push_str("    }\n");
+        }
+        for (block, selector) in self.functions.clone() {
+            let body = self.function(block);
+            out.push_str(&format!(
+                "\n    function function_{:#010x}() public {{\n",
+                selector
+            ));
+            print(&body, 2, &gotos(&body), &mut out);
+            out.push_str("    }\n");
+        }
+        out.push_str("}\n");
+        out
+    }
+
+    fn function(&mut self, entry: usize) -> Vec<Node> {
+        let mut ctx = Context::default();
+        self.region(Some(entry), &mut ctx)
+    }
+
+    fn region(&mut self, start: Option<usize>, ctx: &mut Context) -> Vec<Node> {
+        let depth = ctx.path.len();
+        let mut out = Vec::new();
+        let mut next = start;
+        while let Some(b) = next {
+            next = self.enter(b, ctx, &mut out);
+        }
+        ctx.path.truncate(depth);
+        out
+    }
+
+    fn enter(&mut self, b: usize, ctx: &mut Context, out: &mut Vec<Node>) -> Option<usize> {
+        if self.lines > MAX_LINES {
+            out.push(Node::Line("// ...".into()));
+            return None;
+        }
+        if self.is_loop_header(b) && !ctx.loops.iter().any(|&(header, _)| header == b) {
+            let exit = self.loop_exit(b);
+            let stop = ctx.stop.take();
+            ctx.loops.push((b, exit));
+            let body = self.region(Some(b), ctx);
+            ctx.loops.pop();
+            ctx.stop = stop;
+            out.push(Node::Loop(body));
+            return exit.and_then(|exit| self.transition(exit, ctx, out));
+        }
+        ctx.path.push(b);
+        out.push(Node::Label(b));
+        self.block(b, ctx, out)
+    }
+
+    fn block(&mut self, b: usize, ctx: &mut Context, out: &mut Vec<Node>) -> Option<usize> {
+        let ssa = self.ssa;
+        let block = &ssa.blocks[b];
+        let terminator = block.terminator();
+        let body = match terminator {
+            Some(_) => &block.statements[..block.statements.len() - 1],
+            None => &block.statements[..],
+        };
+        for stmt in body {
+            if let Some(line) = self.statement(stmt, ctx) {
+                self.line(out, line);
+            }
+        }
+        let fallthrough = Some(b + 1).filter(|f| self.succs[b].contains(f));
+        let Some(stmt) = terminator else {
+            return self.follow(b, fallthrough, ctx, out);
+        };
+        match stmt.opcode {
+            Opcode::JUMP => match self.jump_target(b, &stmt.args[0], ctx) {
+                Some(target) => self.edge(b, target, ctx, out),
+                None => {
+                    let line = format!("goto {};", self.value(&stmt.args[0], ctx));
+                    self.line(out, line);
+                    None
+                }
+            },
+            Opcode::JUMPI => {
+                let Some(taken) = self.jump_target(b, &stmt.args[0], ctx) else {
+                    let line = format!(
+                        "if ({}) goto {};",
+                        self.condition(&stmt.args[1], false, ctx),
+                        self.value(&stmt.args[0], ctx)
+                    );
+                    self.line(out, line);
+                    return self.follow(b, fallthrough, ctx, out);
+                };
+                let cond = &stmt.args[1];
+                let (condition, negated) = (
+                    self.condition(cond, false, ctx),
+                    self.condition(cond, true, ctx),
+                );
+                if let Some(selector) = self.functions.get(&taken) {
+                    let call = Node::Line(format!("function_{:#010x}();", selector));
+                    out.push(Node::If(condition, negated, vec![call], vec![]));
+                    return self.follow(b, fallthrough, ctx, out);
+                }
+                if self.reverts[taken] {
+                    self.line(out, format!("require({});", negated));
+                    return self.follow(b, fallthrough, ctx, out);
+                }
+                if fallthrough.is_some_and(|f| self.reverts[f]) {
+                    self.line(out, format!("require({});", condition));
+                    return self.edge(b, taken, ctx, out);
+                }
+                let merge = self.ipdom[b];
+                let stop = core::mem::replace(&mut ctx.stop, merge);
+                let reached = core::mem::replace(&mut ctx.reached, false);
+                let mut then = Vec::new();
+                let next = self.edge(b, taken, ctx, &mut then);
+                then.extend(self.region(next, ctx));
+                let mut otherwise = Vec::new();
+                let next = self.follow(b, fallthrough, ctx, &mut otherwise);
+                otherwise.extend(self.region(next, ctx));
+                let joined = core::mem::replace(&mut ctx.reached, reached);
+                ctx.stop = stop;
+                out.push(Node::If(condition, negated, then, otherwise));
+                match (joined, merge) {
+                    (true, Some(merge)) => self.transition(merge, ctx, out),
+                    _ => None,
+                }
+            }
+            _ => {
+                let line = format!("{};", self.effect(stmt, ctx));
+                self.line(out, line);
+                None
+            }
+        }
+    }
+
+    /// Follows the fallthrough edge, if any, or else halts like running off
+    /// the end of the code would.
+    fn follow(
+        &mut self,
+        from: usize,
+        to: Option<usize>,
+        ctx: &mut Context,
+        out: &mut Vec<Node>,
+    ) -> Option<usize> {
+        match to {
+            Some(to) => self.edge(from, to, ctx, out),
+            None => {
+                self.line(out, "stop();".into());
+                None
+            }
+        }
+    }
+
+    /// Follows the edge from `from` to `to`, assigning `to`'s phi variables.
+    fn edge(
+        &mut self,
+        from: usize,
+        to: usize,
+        ctx: &mut Context,
+        out: &mut Vec<Node>,
+    ) -> Option<usize> {
+        let ssa = self.ssa;
+        let (source, target) = (&ssa.blocks[from], &ssa.blocks[to]);
+        for phi in &target.phis {
+            let Some(value) = source.exit(phi.slot).map(|v| resolve(&ctx.env, v)) else {
+                continue;
+            };
+            match value {
+                SsaValue::Block(_) => {
+                    ctx.env.insert(phi.result, value);
+                }
+                _ if self.live.contains(&phi.result) && value != SsaValue::Var(phi.result) => {
+                    ctx.env.remove(&phi.result);
+                    let line = format!("v{} = {};", phi.result, self.value(&value, ctx));
+                    self.line(out, line);
+                }
+                _ => {
+                    ctx.env.remove(&phi.result);
+                }
+            }
+        }
+        self.transition(to, ctx, out)
+    }
+
+    fn transition(&mut self, to: usize, ctx: &mut Context, out: &mut Vec<Node>) -> Option<usize> {
+        if let Some(&(header, exit)) = ctx.loops.last() {
+            if to == header {
+                out.push(Node::Continue);
+                return None;
+            }
+            if Some(to) == exit {
+                out.push(Node::Break);
+                return None;
+            }
+        }
+        if Some(to) == ctx.stop {
+            ctx.reached = true;
+            return None;
+        }
+        let visits = ctx.path.iter().filter(|&&b| b == to).count();
+        let reenter = self.reentrant[to] && visits < MAX_CALLS;
+        if (visits > 0 && !reenter) || ctx.loops.iter().any(|&(header, _)| header == to) {
+            out.push(Node::Goto(to));
+            return None;
+        }
+        Some(to)
+    }
+
+    fn line(&mut self, out: &mut Vec<Node>, line: String) {
+        self.lines += 1;
+        out.push(Node::Line(line));
+    }
+
+    fn jump_target(&self, b: usize, target: &SsaValue, ctx: &Context) -> Option<usize> {
+        match resolve(&ctx.env, *target) {
+            SsaValue::Var(_) if self.succs[b].len() == 1 => Some(self.succs[b][0]),
+            value => self.target(b, &value),
+        }
+    }
+
+    fn target(&self, b: usize, target: &SsaValue) -> Option<usize> {
+        let SsaValue::Block(pc) = target else {
+            return None;
+        };
+        self.succs[b]
+            .iter()
+            .copied()
+            .find(|&s| self.ssa.blocks[s].start == *pc)
+    }
+
+    fn dominates(&self, a: usize, mut b: usize) -> bool {
+        loop {
+            if a == b {
+                return true;
+            }
+            match self.idom[b] {
+                Some(parent) if parent != b => b = parent,
+                _ => return false,
+            }
+        }
+    }
+
+    fn is_loop_header(&self, b: usize) -> bool {
+        !self.reentrant[b] && self.preds[b].iter().any(|&p| self.dominates(b, p))
+    }
+
+    /// Returns the natural loop headed by this block, which is just the
+    /// block itself if it heads no loop.
+    fn loop_body(&self, header: usize) -> BTreeSet<usize> {
+        let mut body = BTreeSet::from([header]);
+        let mut pending: Vec<usize> = self.preds[header]
+            .iter()
+            .copied()
+            .filter(|&p| self.dominates(header, p))
+            .collect();
+        while let Some(b) = pending.pop() {
+            if body.insert(b) {
+                pending.extend(self.preds[b].iter().copied());
+            }
+        }
+        body
+    }
+
+    fn loop_exit(&self, header: usize) -> Option<usize> {
+        let body = self.loop_body(header);
+        let exits: BTreeSet<usize> = body
+            .iter()
+            .flat_map(|&b| self.succs[b].iter().copied())
+            .filter(|s| !body.contains(s) && !self.reverts[*s])
+            .collect();
+        match exits.len() {
+            1 => exits.first().copied(),
+            _ => None,
+        }
+    }
+
+    /// Returns the selector compared against by a dispatcher condition.
+    fn dispatch(&self, cond: &SsaValue) -> Option<u32> {
+        let stmt = self.def(cond).filter(|stmt| stmt.opcode == Opcode::EQ)?;
+        let (constant, other) = match (stmt.args[0], stmt.args[1]) {
+            (SsaValue::Const(c), other) | (other, SsaValue::Const(c)) => (c, other),
+            _ => return None,
+        };
+        match other {
+            SsaValue::Var(var) if self.is_selector(var) && constant <= u256::from(u32::MAX) => {
+                Some(constant.as_u32())
+            }
+            _ => None,
+        }
+    }
+
+    fn is_selector(&self, var: usize) -> bool {
+        let Some(stmt) = self.def(&SsaValue::Var(var)) else {
+            return false;
+        };
+        let is_calldata = |value: &SsaValue| {
+            self.def(value).is_some_and(|stmt| {
+                stmt.opcode == Opcode::CALLDATALOAD && stmt.args[0] == SsaValue::Const(u256::ZERO)
+            })
+        };
+        match (&stmt.opcode, stmt.args.as_slice()) {
+            (Opcode::SHR, [SsaValue::Const(shift), value]) => *shift == 224 && is_calldata(value),
+            (Opcode::DIV, [value, SsaValue::Const(divisor)]) => {
+                *divisor == u256::ONE << 224 && is_calldata(value)
+            }
+            (Opcode::AND, [SsaValue::Const(mask), SsaValue::Var(value)])
+            | (Opcode::AND, [SsaValue::Var(value), SsaValue::Const(mask)]) => {
+                *mask == u256::from(u32::MAX) && self.is_selector(*value)
+            }
+            _ => false,
+        }
+    }
+
+    fn def(&self, value: &SsaValue) -> Option<&'a SsaStatement> {
+        let SsaValue::Var(var) = value else {
+            return None;
+        };
+        let &(b, i) = self.defs.get(var)?;
+        Some(&self.ssa.blocks[b].statements[i])
+    }
+
+    fn is_inlined(&self, var: usize) -> bool {
+        if self.phi_uses.contains(&var) {
+            return false;
+        }
+        let (Some(&(b, i)), Some([(ub, ui)])) =
+            (self.defs.get(&var), self.uses.get(&var).map(Vec::as_slice))
+        else {
+            return false;
+        };
+        let statements = &self.ssa.blocks[b].statements;
+        let opcode = &statements[i].opcode;
+        is_pure(opcode)
+            || (is_read(opcode)
+                && *ub == b
+                && *ui > i
+                && statements[i + 1..*ui]
+                    .iter()
+                    .all(|stmt| is_pure(&stmt.opcode) || is_read(&stmt.opcode)))
+    }
+
+    fn statement(&self, stmt: &SsaStatement, ctx: &Context) -> Option<String> {
+        let Some(var) = stmt.result else {
+            return Some(format!("{};", self.effect(stmt, ctx)));
+        };
+        let used = self.uses.contains_key(&var) || self.phi_uses.contains(&var);
+        if self.is_selector(var) || self.is_inlined(var) || (!used && is_pure(&stmt.opcode)) {
+            return None;
+        }
+        Some(format!("uint256 v{} = {};", var, self.expr(stmt, ctx)))
+    }
+
+    fn value(&self, value: &SsaValue, ctx: &Context) -> String {
+        match resolve(&ctx.env, *value) {
+            SsaValue::Const(c) => format!("{:#x}", c),
+            SsaValue::Block(pc) => format!("block_{:#x}", pc),
+            SsaValue::Var(var) if self.is_selector(var) => "msg.sig".into(),
+            SsaValue::Var(var) if self.is_inlined(var) => self.expr(self.def(value).unwrap(), ctx),
+            SsaValue::Var(var) => format!("v{}", var),
+        }
+    }
+
+    fn operand(&self, value: &SsaValue, ctx: &Context) -> String {
+        let compound = match resolve(&ctx.env, *value) {
+            SsaValue::Var(var) if self.is_inlined(var) && !self.is_selector(var) => self
+                .def(value)
+                .is_some_and(|stmt| operator(&stmt.opcode).is_some()),
+            _ => false,
+        };
+        match compound {
+            true => format!("({})", self.value(value, ctx)),
+            false => self.value(value, ctx),
+        }
+    }
+
+    fn is_boolean(&self, value: &SsaValue) -> bool {
+        use Opcode::*;
+        match value {
+            SsaValue::Var(var) if self.is_inlined(*var) => self
+                .def(value)
+                .is_some_and(|stmt| matches!(stmt.opcode, LT | GT | SLT | SGT | EQ | ISZERO)),
+            _ => false,
+        }
+    }
+
+    fn condition(&self, value: &SsaValue, negate: bool, ctx: &Context) -> String {
+        if let SsaValue::Var(var) = value {
+            if self.is_inlined(*var) {
+                let stmt = self.def(value).unwrap();
+                if stmt.opcode == Opcode::ISZERO {
+                    return self.condition(&stmt.args[0], !negate, ctx);
+                }
+                if negate && stmt.opcode == Opcode::EQ {
+                    let (a, b) = self.comparands(stmt);
+                    return format!("{} != {}", self.operand(a, ctx), self.operand(b, ctx));
+                }
+            }
+        }
+        match (negate, self.is_boolean(value)) {
+            (false, true) => self.value(value, ctx),
+            (false, false) => format!("{} != 0", self.operand(value, ctx)),
+            (true, true) => format!("!({})", self.value(value, ctx)),
+            (true, false) => format!("{} == 0", self.operand(value, ctx)),
+        }
+    }
+
+    fn comparands<'s>(&self, stmt: &'s SsaStatement) -> (&'s SsaValue, &'s SsaValue) {
+        match (&stmt.args[0], &stmt.args[1]) {
+            (a @ SsaValue::Const(_), b) => (b, a),
+            (a, b) => (a, b),
+        }
+    }
+
+    fn expr(&self, stmt: &SsaStatement, ctx: &Context) -> String {
+        use Opcode::*;
+        let arg = |i: usize| self.operand(&stmt.args[i], ctx);
+        if let Some(op) = operator(&stmt.opcode) {
+            return match stmt.opcode {
+                SHL | SHR => format!("{} {} {}", arg(1), op, arg(0)),
+                EQ => {
+                    let (a, b) = self.comparands(stmt);
+                    let constant = match (a, b) {
+                        (SsaValue::Var(var), SsaValue::Const(c)) if self.is_selector(*var) => {
+                            format!("{:#010x}", c)
+                        }
+                        _ => self.operand(b, ctx),
+                    };
+                    format!("{} == {}", self.operand(a, ctx), constant)
+                }
+                _ => format!("{} {} {}", arg(0), op, arg(1)),
+            };
+        }
+        let name = match stmt.opcode {
+            ISZERO => return self.condition(&stmt.args[0], true, ctx),
+            NOT => return format!("~{}", arg(0)),
+            SLOAD => return format!("storage[{}]", self.value(&stmt.args[0], ctx)),
+            MLOAD => return format!("memory[{}]", self.value(&stmt.args[0], ctx)),
+            SHA3 => return format!("keccak256({})", self.memory(stmt, 0, 1, ctx)),
+            BALANCE => return format!("address({}).balance", self.value(&stmt.args[0], ctx)),
+            CALLDATALOAD => return format!("msg.data[{}]", self.value(&stmt.args[0], ctx)),
+            ADDRESS => "address(this)",
+            ORIGIN => "tx.origin",
+            CALLER => "msg.sender",
+            CALLVALUE => "msg.value",
+            CALLDATASIZE => "msg.data.length",
+            GASPRICE => "tx.gasprice",
+            COINBASE => "block.coinbase",
+            TIMESTAMP => "block.timestamp",
+            NUMBER => "block.number",
+            DIFFICULTY => "block.difficulty",
+            GASLIMIT => "block.gaslimit",
+            CHAINID => "block.chainid",
+            SELFBALANCE => "address(this).balance",
+            BASEFEE => "block.basefee",
+            GAS => "gasleft()",
+            _ => return self.call(stmt, ctx),
+        };
+        name.into()
+    }
+
+    fn effect(&self, stmt: &SsaStatement, ctx: &Context) -> String {
+        use Opcode::*;
+        let value = |i: usize| self.value(&stmt.args[i], ctx);
+        let empty = |i: usize| stmt.args[i] == SsaValue::Const(u256::ZERO);
+        match stmt.opcode {
+            SSTORE => format!("storage[{}] = {}", value(0), value(1)),
+            MSTORE => format!("memory[{}] = {}", value(0), value(1)),
+            MSTORE8 => format!("memory[{}] = uint8({})", value(0), value(1)),
+            CALLDATACOPY => format!(
+                "{} = msg.data[{}]",
+                self.memory(stmt, 0, 2, ctx),
+                self.range(stmt, 1, 2, ctx)
+            ),
+            CODECOPY => format!(
+                "{} = code[{}]",
+                self.memory(stmt, 0, 2, ctx),
+                self.range(stmt, 1, 2, ctx)
+            ),
+            RETURNDATACOPY => format!(
+                "{} = returndata[{}]",
+                self.memory(stmt, 0, 2, ctx),
+                self.range(stmt, 1, 2, ctx)
+            ),
+            STOP => "stop()".into(),
+            RETURN if empty(1) => "return".into(),
+            RETURN => format!("return {}", self.memory(stmt, 0, 1, ctx)),
+            REVERT if empty(1) => "revert()".into(),
+            REVERT => format!("revert({})", self.memory(stmt, 0, 1, ctx)),
+            LOG(n) => {
+                let topics = (2..2 + n as usize).map(|i| format!(", {}", value(i)));
+                format!(
+                    "emit Log{}({}{})",
+                    n,
+                    self.memory(stmt, 0, 1, ctx),
+                    topics.collect::<String>()
+                )
+            }
+            _ if stmt.result.is_some() => self.expr(stmt, ctx),
+            _ => self.call(stmt, ctx),
+        }
+    }
+
+    fn call(&self, stmt: &SsaStatement, ctx: &Context) -> String {
+        let args: Vec<String> = stmt.args.iter().map(|arg| self.value(arg, ctx)).collect();
+        format!(
+            "{}({})",
+            stmt.opcode.to_string().to_lowercase(),
+            args.join(", ")
+        )
+    }
+
+    fn memory(&self, stmt: &SsaStatement, offset: usize, size: usize, ctx: &Context) -> String {
+        format!("memory[{}]", self.range(stmt, offset, size, ctx))
+    }
+
+    fn range(&self, stmt: &SsaStatement, offset: usize, size: usize, ctx: &Context) -> String {
+        match (&stmt.args[offset], &stmt.args[size]) {
+            (SsaValue::Const(offset), SsaValue::Const(size)) => {
+                format!("{:#x}:{:#x}", offset, offset.wrapping_add(*size))
+            }
+            (offset, size) => {
+                let offset = self.operand(offset, ctx);
+                format!("{}:{} + {}", offset, offset, self.operand(size, ctx))
+            }
+        }
+    }
+}
+
+fn resolve(env: &BTreeMap<usize, SsaValue>, value: SsaValue) -> SsaValue {
+    match value {
+        SsaValue::Var(var) => env.get(&var).copied().unwrap_or(value),
+        _ => value,
+    }
+}
+
+fn operator(opcode: &Opcode) -> Option<&'static str> {
+    use Opcode::*;
+    let result = match opcode {
+        ADD => "+",
+        MUL => "*",
+        SUB => "-",
+        DIV => "/",
+        MOD => "%",
+        EXP => "**",
+        LT => "<",
+        GT => ">",
+        EQ => "==",
+        AND => "&",
+        OR => "|",
+        XOR => "^",
+        SHL => "<<",
+        SHR => ">>",
+        _ => return None,
+    };
+    Some(result)
+}
+
+/// Whether the opcode's result depends only on its operands and on the
+/// transaction environment.
+fn is_pure(opcode: &Opcode) -> bool {
+    use Opcode::*;
+    matches!(
+        opcode,
+        ADD | MUL
+            | SUB
+            | DIV
+            | SDIV
+            | MOD
+            | SMOD
+            | ADDMOD
+            | MULMOD
+            | EXP
+            | SIGNEXTEND
+            | LT
+            | GT
+            | SLT
+            | SGT
+            | EQ
+            | ISZERO
+            | AND
+            | OR
+            | XOR
+            | NOT
+            | BYTE
+            | SHL
+            | SHR
+            | SAR
+            | ADDRESS
+            | ORIGIN
+            | CALLER
+            | CALLVALUE
+            | CALLDATALOAD
+            | CALLDATASIZE
+            | CODESIZE
+            | GASPRICE
+            | COINBASE
+            | TIMESTAMP
+            | NUMBER
+            | DIFFICULTY
+            | GASLIMIT
+            | CHAINID
+            | BASEFEE
+    )
+}
+
+/// Whether the opcode reads state that other instructions may modify.
+fn is_read(opcode: &Opcode) -> bool {
+    use Opcode::*;
+    matches!(
+        opcode,
+        SLOAD
+            | MLOAD
+            | SHA3
+            | BALANCE
+            | SELFBALANCE
+            | EXTCODESIZE
+            | EXTCODEHASH
+            | BLOCKHASH
+            | RETURNDATASIZE
+            | GAS
+            | MSIZE
+    )
+}
+
+fn immediate_dominators(
+    entry: usize,
+    succs: &[Vec<usize>],
+    preds: &[Vec<usize>],
+) -> Vec<Option<usize>> {
+    let n = succs.len();
+    let mut order = Vec::new();
+    let mut visited = vec![false; n];
+    let mut stack = vec![(entry, 0)];
+    visited[entry] = true;
+    while let Some((b, i)) = stack.pop() {
+        match succs[b].get(i) {
+            Some(&s) => {
+                stack.push((b, i + 1));
+                if !visited[s] {
+                    visited[s] = true;
+                    stack.push((s, 0));
+                }
+            }
+            None => order.push(b),
+        }
+    }
+    let mut rank = vec![usize::MAX; n];
+    for (i, &b) in order.iter().rev().enumerate() {
+        rank[b] = i;
+    }
+    let mut idom = vec![None; n];
+    idom[entry] = Some(entry);
+    let mut changed = true;
+    while changed {
+        changed = false;
+        for &b in order.iter().rev().filter(|&&b| b != entry) {
+            let mut new: Option<usize> = None;
+            for &p in preds[b].iter().filter(|&&p| idom[p].is_some()) {
+                new = Some(match new {
+                    None => p,
+                    Some(mut q) => {
+                        let mut p = p;
+                        while p != q {
+                            while rank[p] > rank[q] {
+                                p = idom[p].unwrap();
+                            }
+                            while rank[q] > rank[p] {
+                                q = idom[q].unwrap();
+                            }
+                        }
+                        p
+                    }
+                });
+            }
+            if new != idom[b] {
+                idom[b] = new;
+                changed = true;
+            }
+        }
+    }
+    idom
+}
+
+fn gotos(nodes: &[Node]) -> BTreeSet<usize> {
+    let mut result = BTreeSet::new();
+    for node in nodes {
+        match node {
+            Node::Goto(b) => {
+                result.insert(*b);
+            }
+            Node::If(_, _, then, otherwise) => {
+                result.extend(gotos(then));
+                result.extend(gotos(otherwise));
+            }
+            Node::Loop(body) => result.extend(gotos(body)),
+            _ => {}
+        }
+    }
+    result
+}
+
+fn print(nodes: &[Node], indent: usize, labels: &BTreeSet<usize>, out: &mut String) {
+    let pad = "    ".repeat(indent);
+    for (i, node) in nodes.iter().enumerate() {
+        match node {
+            Node::Line(line) => out.push_str(&format!("{}{}\n", pad, line)),
+            Node::Break => out.push_str(&format!("{}break;\n", pad)),
+            Node::Continue if i + 1 == nodes.len() => {}
+            Node::Continue => out.push_str(&format!("{}continue;\n", pad)),
+            Node::Label(b) if labels.contains(b) => {
+                out.push_str(&format!("{}label_{}:\n", "    ".repeat(indent - 1), b))
+            }
+            Node::Label(_) => {}
+            Node::Goto(b) => out.push_str(&format!("{}goto label_{};\n", pad, b)),
+            Node::If(cond, negated, then, otherwise) => {
+                let (cond, then, otherwise) = match is_empty(then, labels) {
+                    true => (negated, otherwise, then),
+                    false => (cond, then, otherwise),
+                };
+                out.push_str(&format!("{}if ({}) {{\n", pad, cond));
+                print(then, indent + 1, labels, out);
+                if !is_empty(otherwise, labels) {
+                    out.push_str(&format!("{}}} else {{\n", pad));
+                    print(otherwise, indent + 1, labels, out);
+                }
+                out.push_str(&format!("{}}}\n", pad));
+            }
+            Node::Loop(body) => {
+                let start = body
+                    .iter()
+                    .position(|node| !matches!(node, Node::Label(b) if !labels.contains(b)));
+                let (cond, body) = match start.map(|i| (&body[i], i + 1 == body.len())) {
+                    Some((Node::If(cond, negated, then, otherwise), true)) => {
+                        match (then.as_slice(), otherwise.as_slice()) {
+                            ([Node::Break], rest) => (negated.as_str(), rest),
+                            (rest, [Node::Break]) => (cond.as_str(), rest),
+                            _ => ("true", body.as_slice()),
+                        }
+                    }
+                    _ => ("true", body.as_slice()),
+                };
+                out.push_str(&format!("{}while ({}) {{\n", pad, cond));
+                print(body, indent + 1, labels, out);
+                out.push_str(&format!("{}}}\n", pad));
+            }
+        }
+    }
+}
+
+fn is_empty(nodes: &[Node], labels: &BTreeSet<usize>) -> bool {
+    nodes
+        .iter()
+        .all(|node| matches!(node, Node::Label(b) if !labels.contains(b)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse::parse_program;
+
+    fn decompile(input: &str) -> String {
+        decompile_program(&parse_program(input).unwrap())
+    }
+
+    fn function(body: &str) -> String {
+        format!(
+            "contract Decompiled {{\n    function __entry__() public {{\n{}    }}\n}}\n",
+            body
+        )
+    }
+
+    #[test]
+    fn if_else() {
+        let input = "CALLVALUE PUSH @else JUMPI PUSH 1 PUSH 0 SSTORE PUSH @end JUMP \
+                     else: JUMPDEST PUSH 2 PUSH 0 SSTORE end: JUMPDEST STOP";
+        let expected = "        if (msg.value != 0) {
+            storage[0x0] = 0x2;
+        } else {
+            storage[0x0] = 0x1;
+        }
+        stop();
+";
+        assert_eq!(decompile(input), function(expected));
+    }
+
+    #[test]
+    fn while_loop() {
+        let input = "PUSH 0 CALLDATALOAD head: JUMPDEST DUP1 ISZERO PUSH @exit JUMPI \
+                     PUSH 1 SWAP1 SUB PUSH @head JUMP exit: JUMPDEST PUSH 0 SSTORE STOP";
+        let expected = "        uint256 v0 = msg.data[0x0];
+        v3 = v0;
+        while (v3 != 0) {
+            uint256 v2 = v3 - 0x1;
+            v3 = v2;
+        }
+        storage[0x0] = v3;
+        stop();
+";
+        assert_eq!(decompile(input), function(expected));
+    }
+
+    #[test]
+    fn early_exits() {
+        let input = "PUSH 0 CALLDATALOAD PUSH @go JUMPI PUSH 32 PUSH 0 RETURN \
+                     go: JUMPDEST PUSH 1 PUSH 0 SSTORE STOP";
+        let expected = "        if (msg.data[0x0] != 0) {
+            storage[0x0] = 0x1;
+            stop();
+        } else {
+            return memory[0x0:0x20];
+        }
+";
+        assert_eq!(decompile(input), function(expected));
+        let input = "CALLVALUE ISZERO PUSH @ok JUMPI PUSH 0 DUP1 REVERT \
+                     ok: JUMPDEST PUSH 1 PUSH 0 SSTORE STOP";
+        let expected = "        require(msg.value == 0);
+        storage[0x0] = 0x1;
+        stop();
+";
+        assert_eq!(decompile(input), function(expected));
+    }
+}
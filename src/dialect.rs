@@ -0,0 +1,397 @@
+// This is free and unencumbered software released into the public domain.
+
+use alloc::{
+    string::{String, ToString},
+    vec::Vec,
+};
+use ethnum::u256;
+#[cfg(feature = "std")]
+use serde_json::{json, Value};
+
+use crate::{
+    assemble::assemble_statements,
+    error::ParseError,
+    opcode::Opcode,
+    parse::{is_label, parse_immediate, parse_opcode, parse_statements, tokens, AsmStatement},
+    program::Program,
+};
+
+/// Parses the syntax of the [etk] assembler, with lowercase mnemonics,
+/// `#` comments, label operands such as `push2 label`, and `%push(label)`.
+///
+/// Other etk macros and expressions are not supported.
+///
+/// [etk]: https://github.com/quilt/etk
+pub fn parse_etk(input: &str) -> Result<Program, ParseError> {
+    let mut text = String::new();
+    for line in input.lines() {
+        let code = line.split('#').next().unwrap_or_default();
+        let mut previous = "";
+        for (column, token) in tokens(code) {
+            // Keep each token at its column, so errors point into the input.
+            while text.len() - text.rfind('\n').map_or(0, |i| i + 1) + 1 < column {
+                text.push(' ');
+            }
+            let operand = match token
+                .strip_prefix("%push(")
+                .and_then(|t| t.strip_suffix(')'))
+            {
+                Some(operand) => {
+                    text.push_str("PUSH ");
+                    operand
+                }
+                None if is_push(previous) => token,
+                None => {
+                    text.push_str(token);
+                    previous = token;
+                    continue;
+                }
+            };
+            if is_label(operand) {
+                text.push('@');
+            }
+            text.push_str(operand);
+            previous = token;
+        }
+        text.push('\n');
+    }
+    assemble_statements(&parse_statements(&text)?)
+}
+
+/// Prints a program in the syntax of the etk assembler, one instruction
+/// per line.
+pub fn print_etk(program: &Program) -> String {
+    let mut result = String::new();
+    for op in &program.0 {
+        let text = match op {
+            Opcode::SHA3 => "keccak256".to_string(),
+            _ => op.to_string().to_ascii_lowercase(),
+        };
+        result.push_str(&text);
+        result.push('\n');
+    }
+    result
+}
+
+/// Parses the text assembly printed by `solc --asm`, selecting the
+/// sub-assembly at `path`, e.g. `&[0]` for the `sub_0` runtime code of a
+/// contract's creation code.
+///
+/// Tags define a `JUMPDEST`, and tag references are pushed with a common
+/// width as solc does. Comments, `auxdata` and the functional form such as
+/// `mstore(0x40, 0x80)` are understood, immutables are pushed as zero
+/// placeholders, but references to sub-assemblies and data such as
+/// `dataSize(sub_0)` are not supported.
+pub fn parse_solc_asm(input: &str, path: &[usize]) -> Result<Program, ParseError> {
+    let mut statements = Vec::new();
+    let mut current: Vec<usize> = Vec::new();
+    let mut commented = false;
+    for (line, text) in input.lines().enumerate() {
+        let line = line + 1;
+        let code = strip_block_comments(text, &mut commented);
+        let code = code.split("//").next().unwrap_or_default();
+        let trimmed = code.trim();
+        if let Some(sub) = trimmed.strip_suffix(": assembly {") {
+            let index = sub
+                .strip_prefix("sub_")
+                .and_then(|index| index.parse().ok())
+                .ok_or(ParseError::Unsupported(line, 1))?;
+            current.push(index);
+            continue;
+        }
+        if trimmed == "}" {
+            current.pop();
+            continue;
+        }
+        let skipped = trimmed.is_empty()
+            || trimmed.starts_with("=======")
+            || trimmed.starts_with("auxdata:")
+            || trimmed == "EVM assembly:";
+        if current != path || skipped {
+            continue;
+        }
+        let column = code.len() - code.trim_start().len() + 1;
+        if let Some(tag) = trimmed.strip_suffix(':') {
+            if !is_tag(tag) {
+                return Err(ParseError::InvalidLabel(line, column));
+            }
+            statements.push(AsmStatement::Label(tag.to_string()));
+            statements.push(AsmStatement::Opcode(Opcode::JUMPDEST));
+            continue;
+        }
+        let mut items = items(code).into_iter().peekable();
+        let item = parse_item(&mut items, line)?;
+        if let Some((column, _)) = items.next() {
+            return Err(ParseError::Unsupported(line, column));
+        }
+        emit(&item, line, &mut statements)?;
+    }
+    assemble_tags(statements)
+}
+
+/// Parses the JSON assembly printed by `solc --asm-json`, selecting the
+/// sub-assembly at `path` from the nested `.data` objects.
+///
+/// Immutables are pushed as zero placeholders, but references to
+/// sub-assemblies and data are not supported. Errors in items are
+/// positioned at line 0, with the item's index from 1 as the column.
+#[cfg(feature = "std")]
+pub fn parse_evmasm_json(input: &str, path: &[usize]) -> Result<Program, ParseError> {
+    let root: Value = serde_json::from_str(input)
+        .map_err(|err| ParseError::InvalidJson(err.line(), err.column()))?;
+    let mut assembly = &root;
+    for index in path {
+        assembly = &assembly[".data"][index.to_string()];
+    }
+    let items = assembly[".code"]
+        .as_array()
+        .ok_or(ParseError::InvalidJson(0, 0))?;
+    let mut statements = Vec::new();
+    for (index, item) in items.iter().enumerate() {
+        let position = (0, index + 1);
+        let invalid = ParseError::InvalidJson(position.0, position.1);
+        let unsupported = ParseError::Unsupported(position.0, position.1);
+        let name = item["name"].as_str().ok_or(invalid.clone())?;
+        let value = item["value"].as_str();
+        let statement = match name {
+            "PUSH" => {
+                let digits = value.ok_or(invalid.clone())?;
+                let value = parse_immediate(&format!("0x{}", digits)).ok_or(invalid.clone())?;
+                // Leading zeros in the value widen the PUSH.
+                let width = digits.len().div_ceil(2).max(1) as u8;
+                AsmStatement::Opcode(Opcode::push_sized(width, value).map_err(|_| invalid.clone())?)
+            }
+            "PUSH [tag]" => AsmStatement::PushLabel {
+                width: None,
+                label: format!("tag_{}", value.ok_or(invalid.clone())?),
+                line: position.0,
+                column: position.1,
+            },
+            "tag" => AsmStatement::Label(format!("tag_{}", value.ok_or(invalid.clone())?)),
+            "PUSHIMMUTABLE" => AsmStatement::Opcode(Opcode::push_sized(32, u256::ZERO).unwrap()),
+            _ => AsmStatement::Opcode(parse_opcode(name).ok_or(unsupported)?),
+        };
+        statements.push(statement);
+    }
+    assemble_tags(statements)
+}
+
+/// Prints a program as the JSON assembly of `solc --asm-json`, pushing
+/// values with their leading zeros so that PUSH widths are kept.
+#[cfg(feature = "std")]
+pub fn print_evmasm_json(program: &Program) -> String {
+    let items: Vec<Value> = program
+        .0
+        .iter()
+        .map(|op| {
+            let item = |name: &str| json!({"begin": 0, "end": 0, "name": name, "source": -1});
+            match op {
+                Opcode::PUSH(push) if push.width() > 0 => {
+                    let mut item = item("PUSH");
+                    item["value"] = json!(hex::encode_upper(push.bytes()));
+                    item
+                }
+                Opcode::SHA3 => item("KECCAK256"),
+                _ => item(&op.to_string()),
+            }
+        })
+        .collect();
+    json!({ ".code": items }).to_string()
+}
+
+/// Assembles statements with every tag pushed at the smallest common width
+/// that fits all tags.
+fn assemble_tags(statements: Vec<AsmStatement>) -> Result<Program, ParseError> {
+    let mut width = 1;
+    loop {
+        let sized: Vec<AsmStatement> = statements
+            .iter()
+            .cloned()
+            .map(|stmt| match stmt {
+                AsmStatement::PushLabel {
+                    label,
+                    line,
+                    column,
+                    ..
+                } => AsmStatement::PushLabel {
+                    width: Some(width),
+                    label,
+                    line,
+                    column,
+                },
+                stmt => stmt,
+            })
+            .collect();
+        match assemble_statements(&sized) {
+            Err(ParseError::LabelOutOfRange(..)) if width < 32 => width += 1,
+            result => return result,
+        }
+    }
+}
+
+/// An item of solc's functional assembly, such as `mstore(0x40, 0x80)`.
+enum Item<'a> {
+    Word(usize, &'a str),
+    Call(usize, &'a str, Vec<Item<'a>>),
+}
+
+/// Splits a line into words, parentheses and commas, with their columns.
+fn items(code: &str) -> Vec<(usize, &str)> {
+    let mut result = Vec::new();
+    let mut start = None;
+    let mut quoted = false;
+    for (i, c) in code.char_indices().chain([(code.len(), ' ')]) {
+        let delimiter = !quoted && (c.is_whitespace() || matches!(c, '(' | ')' | ','));
+        if c == '"' {
+            quoted = !quoted;
+        }
+        match (delimiter, start) {
+            (true, Some(s)) => {
+                result.push((s + 1, &code[s..i]));
+                start = None;
+            }
+            (false, None) => start = Some(i),
+            _ => {}
+        }
+        if delimiter && !c.is_whitespace() && i < code.len() {
+            result.push((i + 1, &code[i..i + 1]));
+        }
+    }
+    result
+}
+
+fn parse_item<'a>(
+    items: &mut core::iter::Peekable<alloc::vec::IntoIter<(usize, &'a str)>>,
+    line: usize,
+) -> Result<Item<'a>, ParseError> {
+    let (column, word) = items.next().ok_or(ParseError::Unsupported(line, 1))?;
+    if matches!(word, "(" | ")" | ",") {
+        return Err(ParseError::Unsupported(line, column));
+    }
+    if items.peek().map(|(_, t)| *t) != Some("(") {
+        return Ok(Item::Word(column, word));
+    }
+    items.next();
+    let mut args = Vec::new();
+    if items.peek().map(|(_, t)| *t) == Some(")") {
+        items.next();
+        return Ok(Item::Call(column, word, args));
+    }
+    loop {
+        args.push(parse_item(items, line)?);
+        match items.next() {
+            Some((_, ",")) => continue,
+            Some((_, ")")) => return Ok(Item::Call(column, word, args)),
+            Some((column, _)) => return Err(ParseError::Unsupported(line, column)),
+            None => return Err(ParseError::Unsupported(line, column)),
+        }
+    }
+}
+
+/// Emits the statements of an item, pushing a call's arguments from last
+/// to first before its instruction.
+fn emit(item: &Item, line: usize, statements: &mut Vec<AsmStatement>) -> Result<(), ParseError> {
+    match item {
+        Item::Call(_, "immutable", _) => {
+            statements.push(AsmStatement::Opcode(
+                Opcode::push_sized(32, u256::ZERO).unwrap(),
+            ));
+        }
+        Item::Call(column, name, args) => {
+            let op = parse_opcode(&name.to_ascii_uppercase())
+                .filter(|op| !op.is_push())
+                .ok_or(ParseError::Unsupported(line, *column))?;
+            for arg in args.iter().rev() {
+                emit(arg, line, statements)?;
+            }
+            statements.push(AsmStatement::Opcode(op));
+        }
+        Item::Word(column, word) if is_tag(word) => statements.push(AsmStatement::PushLabel {
+            width: None,
+            label: word.to_string(),
+            line,
+            column: *column,
+        }),
+        Item::Word(column, word) => {
+            let op = match parse_immediate(word) {
+                Some(value) => Opcode::push(value),
+                None => parse_opcode(&word.to_ascii_uppercase())
+                    .filter(|op| !op.is_push())
+                    .ok_or(ParseError::Unsupported(line, *column))?,
+            };
+            statements.push(AsmStatement::Opcode(op));
+        }
+    }
+    Ok(())
+}
+
+fn is_tag(word: &str) -> bool {
+    word.strip_prefix("tag_")
+        .is_some_and(|n| !n.is_empty() && n.bytes().all(|b| b.is_ascii_digit()))
+}
+
+fn is_push(token: &str) -> bool {
+    parse_opcode(&token.to_ascii_uppercase()).is_some_and(|op| op.size() > 1)
+}
+
+/// Replaces `/* ... */` comments, which may span lines, with spaces.
+fn strip_block_comments(line: &str, commented: &mut bool) -> String {
+    let mut result = String::new();
+    let mut rest = line;
+    loop {
+        let (delimiter, replace) = match *commented {
+            true => ("*/", true),
+            false => ("/*", false),
+        };
+        let Some(i) = rest.find(delimiter) else {
+            match replace {
+                true => result.extend(rest.chars().map(|_| ' ')),
+                false => result.push_str(rest),
+            }
+            return result;
+        };
+        match replace {
+            true => result.extend(rest[..i + 2].chars().map(|_| ' ')),
+            false => {
+                result.push_str(&rest[..i]);
+                result.push_str("  ");
+            }
+        }
+        rest = &rest[i + 2..];
+        *commented = !*commented;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse::parse_program;
+
+    fn program() -> Program {
+        parse_program(
+            "PUSH0 CALLDATALOAD PUSH @skip JUMPI
+            PUSH32 0xffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffff POP
+            PUSH2 0x0001 SHA3
+            skip: JUMPDEST PUSH @end JUMP
+            INVALID
+            end: JUMPDEST STOP",
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn etk_round_trip() {
+        let program = program();
+        let text = print_etk(&program);
+        assert!(text.contains("keccak256\n"), "{}", text);
+        assert_eq!(parse_etk(&text), Ok(program));
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn evmasm_json_round_trip() {
+        let program = program();
+        let json = print_evmasm_json(&program);
+        assert_eq!(parse_evmasm_json(&json, &[]), Ok(program));
+    }
+}
@@ -0,0 +1,352 @@
+// This is free and unencumbered software released into the public domain.
+
+use alloc::{
+    collections::{BTreeMap, BTreeSet},
+    format,
+    string::String,
+    vec,
+    vec::Vec,
+};
+
+use crate::{cfg::build_cfg, dispatch::extract_functions, opcode::Opcode, program::Program};
+
+const CONTEXT: usize = 3;
+
+/// A step of an edit script turning one instruction sequence into another,
+/// with indices into the old and new sequences.
+#[derive(Clone, Copy, Debug, Hash, PartialEq, Eq)]
+pub enum Edit {
+    Equal(usize, usize),
+    Delete(usize),
+    Insert(usize),
+}
+
+/// Computes a shortest edit script between the instructions of two
+/// programs, optionally comparing them with their PUSH immediates zeroed.
+pub fn diff_programs(old: &Program, new: &Program, ignore_immediates: bool) -> Vec<Edit> {
+    diff_opcodes(&old.0, &new.0, ignore_immediates)
+}
+
+/// Computes a shortest edit script with Myers' algorithm.
+pub fn diff_opcodes(old: &[Opcode], new: &[Opcode], ignore_immediates: bool) -> Vec<Edit> {
+    let normalize = |ops: &[Opcode]| -> Vec<Opcode> {
+        match ignore_immediates {
+            true => ops.iter().map(Opcode::zeroed).collect(),
+            false => ops.to_vec(),
+        }
+    };
+    let mut result = Vec::new();
+    myers(&normalize(old), &normalize(new), (0, 0), &mut result);
+    result
+}
+
+/// Appends a shortest edit script between two sequences, which start at
+/// the given indices, using the linear-space variant of Myers' algorithm:
+/// the sequences are split at a point on an optimal path, found from both
+/// ends at once, and each half is diffed in turn.
+fn myers(a: &[Opcode], b: &[Opcode], (i, j): (usize, usize), result: &mut Vec<Edit>) {
+    let prefix = a.iter().zip(b).take_while(|(x, y)| x == y).count();
+    let suffix = a[prefix..]
+        .iter()
+        .rev()
+        .zip(b[prefix..].iter().rev())
+        .take_while(|(x, y)| x == y)
+        .count();
+    result.extend((0..prefix).map(|k| Edit::Equal(i + k, j + k)));
+    let (a, b) = (&a[prefix..a.len() - suffix], &b[prefix..b.len() - suffix]);
+    let (i, j) = (i + prefix, j + prefix);
+    match (a.is_empty(), b.is_empty()) {
+        (true, _) => result.extend((0..b.len()).map(|k| Edit::Insert(j + k))),
+        (_, true) => result.extend((0..a.len()).map(|k| Edit::Delete(i + k))),
+        _ => {
+            let (x, y) = middle(a, b);
+            myers(&a[..x], &b[..y], (i, j), result);
+            myers(&a[x..], &b[y..], (i + x, j + y), result);
+        }
+    }
+    let (i, j) = (i + a.len(), j + b.len());
+    result.extend((0..suffix).map(|k| Edit::Equal(i + k, j + k)));
+}
+
+/// Returns a point, other than either end, on a shortest edit path between
+/// two sequences that differ in their first and last items, by extending
+/// paths forward from the start and backward from the end until they meet.
+fn middle(a: &[Opcode], b: &[Opcode]) -> (usize, usize) {
+    let (n, m) = (a.len() as isize, b.len() as isize);
+    let delta = n - m;
+    let max = (n + m + 1) / 2;
+    let offset = max + 1;
+    // The furthest x reached on each diagonal k = x - y, counting from the
+    // start forward and from the end backward, or -1 where none is.
+    let mut forward = vec![-1isize; 2 * max as usize + 3];
+    let mut backward = forward.clone();
+    let reached = |v: &[isize], k: isize, d: isize| match k.abs() <= d {
+        true => Some(v[(k + offset) as usize]).filter(|&x| x >= 0),
+        false => None,
+    };
+    for d in 0..=max {
+        for forwards in [true, false] {
+            let (v, other) = match forwards {
+                true => (&mut forward, &backward),
+                false => (&mut backward, &forward),
+            };
+            for k in (-d..=d).step_by(2) {
+                let x = match d {
+                    0 => Some(0),
+                    _ => {
+                        let down = reached(v, k + 1, d - 1).filter(|&x| x - k <= m);
+                        let right = reached(v, k - 1, d - 1).map(|x| x + 1).filter(|&x| x <= n);
+                        down.max(right)
+                    }
+                };
+                let Some(mut x) = x else {
+                    v[(k + offset) as usize] = -1;
+                    continue;
+                };
+                let mut y = x - k;
+                while x < n && y < m && {
+                    let (p, q) = match forwards {
+                        true => (x, y),
+                        false => (n - 1 - x, m - 1 - y),
+                    };
+                    a[p as usize] == b[q as usize]
+                } {
+                    x += 1;
+                    y += 1;
+                }
+                v[(k + offset) as usize] = x;
+                // The paths meet once they overlap on a diagonal, checked
+                // after the forward step if the difference is odd and after
+                // the backward step if it is even.
+                let rounds = if forwards { d - 1 } else { d };
+                if (delta % 2 != 0) == forwards
+                    && reached(other, delta - k, rounds).is_some_and(|z| x + z >= n)
+                {
+                    let (x, y) = match forwards {
+                        true => (x, y),
+                        false => (n - x, m - y),
+                    };
+                    return (x as usize, y as usize);
+                }
+            }
+        }
+    }
+    unreachable!("shortest edit paths always meet")
+}
+
+/// Renders an edit script as unified-diff text, one instruction per line,
+/// with hunk positions counting instructions from 1.
+pub fn unified_diff(old: &[Opcode], new: &[Opcode], edits: &[Edit]) -> String {
+    let mut result = String::new();
+    let changed: Vec<usize> = edits
+        .iter()
+        .enumerate()
+        .filter(|(_, edit)| !matches!(edit, Edit::Equal(..)))
+        .map(|(i, _)| i)
+        .collect();
+    let mut start = 0;
+    while start < changed.len() {
+        let mut end = start;
+        while end + 1 < changed.len() && changed[end + 1] - changed[end] <= 2 * CONTEXT + 1 {
+            end += 1;
+        }
+        let first = changed[start].saturating_sub(CONTEXT);
+        let last = (changed[end] + CONTEXT + 1).min(edits.len());
+        let hunk = &edits[first..last];
+        let (old_start, new_start) = position(&edits[..first]);
+        let old_len = hunk
+            .iter()
+            .filter(|e| !matches!(e, Edit::Insert(_)))
+            .count();
+        let new_len = hunk
+            .iter()
+            .filter(|e| !matches!(e, Edit::Delete(_)))
+            .count();
+        result.push_str(&format!(
+            "@@ -{},{} +{},{} @@\n",
+            old_start + usize::from(old_len > 0),
+            old_len,
+            new_start + usize::from(new_len > 0),
+            new_len
+        ));
+        for edit in hunk {
+            match *edit {
+                Edit::Equal(i, _) => result.push_str(&format!(" {}\n", old[i])),
+                Edit::Delete(i) => result.push_str(&format!("-{}\n", old[i])),
+                Edit::Insert(j) => result.push_str(&format!("+{}\n", new[j])),
+            }
+        }
+        start = end + 1;
+    }
+    result
+}
+
+/// Returns how many old and new instructions precede an edit.
+fn position(edits: &[Edit]) -> (usize, usize) {
+    edits.iter().fold((0, 0), |(old, new), edit| match edit {
+        Edit::Equal(..) => (old + 1, new + 1),
+        Edit::Delete(_) => (old + 1, new),
+        Edit::Insert(_) => (old, new + 1),
+    })
+}
+
+/// The functions of two programs compared by selector.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct FunctionDiff {
+    pub added: Vec<u32>,
+    pub removed: Vec<u32>,
+    pub changed: Vec<FunctionChange>,
+    pub unchanged: Vec<u32>,
+}
+
+/// A function present in both programs whose code differs.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct FunctionChange {
+    pub selector: u32,
+    pub old: Vec<Opcode>,
+    pub new: Vec<Opcode>,
+    pub edits: Vec<Edit>,
+}
+
+impl FunctionDiff {
+    /// Renders the diff as unified-diff text, with a hunk group per changed
+    /// function.
+    pub fn to_unified(&self) -> String {
+        let mut result = String::from("--- old\n+++ new\n");
+        for selector in &self.removed {
+            result.push_str(&format!("-function {:#010x}\n", selector));
+        }
+        for selector in &self.added {
+            result.push_str(&format!("+function {:#010x}\n", selector));
+        }
+        for change in &self.changed {
+            result.push_str(&format!("@@@ function {:#010x} @@@\n", change.selector));
+            result.push_str(&unified_diff(&change.old, &change.new, &change.edits));
+        }
+        result
+    }
+}
+
+/// Compares the functions of two programs by selector, where the code of
+/// a function is that of every block reachable from its entry.
+pub fn diff_functions(old: &Program, new: &Program, ignore_immediates: bool) -> FunctionDiff {
+    let (a, b) = (function_bodies(old), function_bodies(new));
+    let mut result = FunctionDiff::default();
+    for (selector, body) in &a {
+        let Some(other) = b.get(selector) else {
+            result.removed.push(*selector);
+            continue;
+        };
+        let edits = diff_opcodes(body, other, ignore_immediates);
+        match edits.iter().all(|edit| matches!(edit, Edit::Equal(..))) {
+            true => result.unchanged.push(*selector),
+            false => result.changed.push(FunctionChange {
+                selector: *selector,
+                old: body.clone(),
+                new: other.clone(),
+                edits,
+            }),
+        }
+    }
+    result.added = b.keys().filter(|s| !a.contains_key(s)).copied().collect();
+    result
+}
+
+pub(crate) fn function_bodies(program: &Program) -> BTreeMap<u32, Vec<Opcode>> {
+    let cfg = build_cfg(program);
+    let mut result = BTreeMap::new();
+    for function in extract_functions(program) {
+        let Some(entry) = cfg.block_at(function.entry) else {
+            continue;
+        };
+        let mut blocks = BTreeSet::new();
+        let mut pending = vec![entry];
+        while let Some(block) = pending.pop() {
+            if blocks.insert(block) {
+                pending.extend(cfg.successors[block].iter().copied());
+            }
+        }
+        let body = blocks
+            .iter()
+            .flat_map(|&block| cfg.blocks[block].opcodes.iter().cloned())
+            .collect();
+        result.insert(function.selector, body);
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse::parse_program;
+
+    #[test]
+    fn immediates() {
+        let old = parse_program("PUSH1 0x01 ADD STOP").unwrap();
+        let new = parse_program("PUSH1 0x02 ADD STOP").unwrap();
+        let edits = diff_programs(&old, &new, false);
+        use Edit::*;
+        assert_eq!(edits, vec![Delete(0), Insert(0), Equal(1, 1), Equal(2, 2)]);
+        assert_eq!(
+            unified_diff(&old.0, &new.0, &edits),
+            "@@ -1,3 +1,3 @@\n-PUSH1 0x01\n+PUSH1 0x02\n ADD\n STOP\n"
+        );
+        let edits = diff_programs(&old, &new, true);
+        assert_eq!(edits, vec![Equal(0, 0), Equal(1, 1), Equal(2, 2)]);
+        assert_eq!(unified_diff(&old.0, &new.0, &edits), "");
+    }
+
+    #[test]
+    fn hunks() {
+        let old = parse_program(&"ADD\n".repeat(20)).unwrap();
+        let new = parse_program(&format!("MUL\n{}MUL\n", "ADD\n".repeat(20))).unwrap();
+        let edits = diff_programs(&old, &new, false);
+        let text = unified_diff(&old.0, &new.0, &edits);
+        assert_eq!(
+            text,
+            "@@ -1,3 +1,4 @@\n+MUL\n ADD\n ADD\n ADD\n@@ -18,3 +19,4 @@\n ADD\n ADD\n ADD\n+MUL\n"
+        );
+    }
+
+    fn dispatcher(functions: &[(u32, &str)]) -> Program {
+        let mut text = String::from("PUSH0 CALLDATALOAD PUSH1 0xe0 SHR\n");
+        for (selector, _) in functions {
+            text.push_str(&format!(
+                "DUP1 PUSH4 {:#010x} EQ PUSH @f{:x} JUMPI\n",
+                selector, selector
+            ));
+        }
+        text.push_str("PUSH0 DUP1 REVERT\n");
+        for (selector, body) in functions {
+            text.push_str(&format!("f{:x}: JUMPDEST {}\n", selector, body));
+        }
+        parse_program(&text).unwrap()
+    }
+
+    #[test]
+    fn functions() {
+        let old = dispatcher(&[
+            (0x11111111, "PUSH1 0x01 PUSH0 SSTORE STOP"),
+            (0x22222222, "CALLER PUSH0 SSTORE STOP"),
+            (0x33333333, "STOP"),
+        ]);
+        let new = dispatcher(&[
+            (0x11111111, "PUSH1 0x07 PUSH0 SSTORE STOP"),
+            (0x22222222, "CALLER PUSH0 SSTORE STOP"),
+            (0x44444444, "ORIGIN PUSH0 SSTORE STOP"),
+        ]);
+        let diff = diff_functions(&old, &new, false);
+        assert_eq!(diff.added, vec![0x44444444]);
+        assert_eq!(diff.removed, vec![0x33333333]);
+        assert_eq!(diff.unchanged, vec![0x22222222]);
+        assert_eq!(diff.changed.len(), 1);
+        assert_eq!(diff.changed[0].selector, 0x11111111);
+        let text = diff.to_unified();
+        assert!(text.starts_with("--- old\n+++ new\n-function 0x33333333\n+function 0x44444444\n"));
+        assert!(text.contains("@@@ function 0x11111111 @@@\n"), "{}", text);
+        assert!(text.contains("-PUSH1 0x01\n+PUSH1 0x07\n"), "{}", text);
+        let diff = diff_functions(&old, &new, true);
+        assert_eq!(diff.unchanged, vec![0x11111111, 0x22222222]);
+        assert!(diff.changed.is_empty());
+    }
+}
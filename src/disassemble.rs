@@ -0,0 +1,163 @@
+// This is free and unencumbered software released into the public domain.
+
+use alloc::{
+    collections::{BTreeMap, BTreeSet},
+    format,
+    string::{String, ToString},
+    vec,
+    vec::Vec,
+};
+use ethnum::u256;
+
+use crate::{
+    cfg::build_cfg,
+    encode::{encode_opcode, immediate},
+    opcode::Opcode,
+    program::Program,
+};
+
+#[derive(Clone, Copy, Debug, Hash, PartialEq, Eq)]
+pub struct ListingOptions {
+    /// Whether to define a label at each jump target, and push it in place
+    /// of the target's PC before a jump.
+    pub labels: bool,
+    /// Whether to annotate jumps with their resolved targets.
+    pub jumps: bool,
+}
+
+impl Default for ListingOptions {
+    fn default() -> Self {
+        ListingOptions {
+            labels: true,
+            jumps: true,
+        }
+    }
+}
+
+/// Disassembles a program into a listing with a line per instruction, of
+/// its PC, its bytes, and the instruction itself.
+///
+/// The listing parses back into the same program with [`parse_program`],
+/// which skips the PC and bytes columns.
+///
+/// [`parse_program`]: crate::parse_program
+pub fn disassemble_program(program: &Program, options: ListingOptions) -> String {
+    let cfg = build_cfg(program);
+    // The resolved targets of each jump instruction.
+    let mut targets: BTreeMap<usize, BTreeSet<usize>> = BTreeMap::new();
+    for (b, block) in cfg.blocks.iter().enumerate() {
+        let Some((pc, op)) = block.instructions().last() else {
+            continue;
+        };
+        if !op.is_jump() {
+            continue;
+        }
+        let successors = cfg.successors[b]
+            .iter()
+            .filter(|&&s| *op == Opcode::JUMP || s != b + 1);
+        targets.insert(pc, successors.map(|&s| cfg.blocks[s].start).collect());
+    }
+    let labeled: BTreeSet<usize> = match options.labels {
+        true => targets.values().flatten().copied().collect(),
+        false => BTreeSet::new(),
+    };
+    let name = |pc: usize| match options.labels {
+        true => label(pc),
+        false => format!("{:#06x}", pc),
+    };
+    // Align the bytes column to the longest instruction up to a PUSH7.
+    let width = program
+        .0
+        .iter()
+        .map(|op| 2 * op.size())
+        .max()
+        .unwrap_or(0)
+        .min(16);
+    let mut result = String::new();
+    let mut instructions = program.instructions().peekable();
+    while let Some((pc, op)) = instructions.next() {
+        if labeled.contains(&pc) {
+            result.push_str(&format!("{}:\n", label(pc)));
+        }
+        let mut bytes = vec![encode_opcode(op)];
+        bytes.extend_from_slice(immediate(op));
+        let mut text = op.to_string();
+        // Push the label of a target jumped to by the next instruction.
+        if let Some(target) = push_value(op).and_then(|value| usize::try_from(value).ok()) {
+            let jumps = instructions
+                .peek()
+                .is_some_and(|(next, _)| targets.get(next).is_some_and(|t| t.contains(&target)));
+            // A PUSH0 takes no label, as it has no immediate to hold one.
+            if jumps && labeled.contains(&target) && op.size() > 1 {
+                text = format!("PUSH{} @{}", op.size() - 1, label(target));
+            }
+        }
+        if let Some(targets) = targets.get(&pc).filter(|t| options.jumps && !t.is_empty()) {
+            let targets: Vec<String> = targets.iter().map(|&t| name(t)).collect();
+            text = format!("{} ; -> {}", text, targets.join(", "));
+        }
+        result.push_str(&format!(
+            "{:#06x}  {:width$}  {}\n",
+            pc,
+            hex::encode(bytes),
+            text,
+            width = width
+        ));
+    }
+    result
+}
+
+fn label(pc: usize) -> String {
+    format!("label_{:04x}", pc)
+}
+
+fn push_value(op: &Opcode) -> Option<u256> {
+    match op {
+        Opcode::PUSH(push) => Some(push.value()),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{decode::decode_program, parse::parse_program};
+
+    #[test]
+    fn listing_round_trip() {
+        let program = parse_program(
+            "PUSH0 CALLDATALOAD PUSH @skip JUMPI
+            PUSH32 0xffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffff POP
+            skip: JUMPDEST PUSH @end JUMP
+            INVALID
+            end: JUMPDEST STOP",
+        )
+        .unwrap();
+        for labels in [true, false] {
+            for jumps in [true, false] {
+                let options = ListingOptions { labels, jumps };
+                let listing = disassemble_program(&program, options);
+                assert_eq!(parse_program(&listing), Ok(program.clone()), "{}", listing);
+            }
+        }
+        let listing = disassemble_program(&program, ListingOptions::default());
+        assert!(listing.contains("label_0027:\n"), "{}", listing);
+        assert!(listing.contains("PUSH1 @label_0027\n"), "{}", listing);
+        assert!(listing.contains("JUMPI ; -> label_0027\n"), "{}", listing);
+    }
+
+    #[test]
+    fn push0_jump_round_trip() {
+        let program = decode_program(&[0x5b, 0x5f, 0x56]).unwrap();
+        for labels in [true, false] {
+            let options = ListingOptions {
+                labels,
+                jumps: true,
+            };
+            let listing = disassemble_program(&program, options);
+            assert_eq!(parse_program(&listing), Ok(program.clone()), "{}", listing);
+        }
+        let listing = disassemble_program(&program, ListingOptions::default());
+        assert!(listing.contains("PUSH0\n"), "{}", listing);
+    }
+}
@@ -0,0 +1,416 @@
+// This is free and unencumbered software released into the public domain.
+
+use alloc::{
+    collections::{BTreeMap, BTreeSet},
+    vec,
+    vec::Vec,
+};
+use ethnum::u256;
+
+use crate::{
+    cfg::{build_cfg, Cfg},
+    eval::eval_opcode,
+    lift::{Statement, Value},
+    opcode::Opcode,
+    program::Program,
+    symbolic::{Expr, SymbolicState},
+};
+
+const MAX_PROLOGUE: usize = 4;
+
+/// The most buckets of a selector jump table that are followed.
+const MAX_BUCKETS: usize = 1024;
+
+/// An external function found in a contract's dispatcher.
+#[derive(Clone, Copy, Debug, Hash, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Function {
+    pub selector: u32,
+    /// The code address at which the function's body starts.
+    pub entry: usize,
+    /// Whether the function accepts ether, i.e. does not revert on a
+    /// non-zero CALLVALUE.
+    pub payable: bool,
+}
+
+/// Extracts the external functions of a contract by recognizing the
+/// comparisons of the calldata selector in its dispatcher.
+///
+/// This covers both the linear and the binary-search (GT/LT split)
+/// dispatchers emitted by solc, as well as Vyper's EQ/XOR comparisons.
+/// The selector is tracked along resolved jumps, and into the buckets of
+/// Vyper's sparse hash-table dispatch, which jumps to the entry read with
+/// `CODECOPY` from a table indexed by the selector modulo the number of
+/// buckets.
+pub fn extract_functions(program: &Program) -> Vec<Function> {
+    let dispatcher = Dispatcher::new(program);
+    let nonpayable = dispatcher.rejects_value(0);
+    let mut result: Vec<Function> = (0..dispatcher.cfg.blocks.len())
+        .filter_map(|b| {
+            let entry = dispatcher.entry(b)?;
+            Some(Function {
+                selector: dispatcher.comparison(b)?.0,
+                entry: dispatcher.cfg.blocks[entry].start,
+                payable: !nonpayable && !dispatcher.rejects_value(entry),
+            })
+        })
+        .collect();
+    result.sort();
+    result.dedup_by_key(|function| function.selector);
+    result
+}
+
+/// Returns the initial states for executing a program symbolically from
+/// its start and from the entry of each of its functions, where the stack
+/// holds just the selector as left by the dispatcher.
+pub(crate) fn entry_states(program: &Program) -> Vec<SymbolicState> {
+    let mut result = vec![SymbolicState::default()];
+    for function in extract_functions(program) {
+        result.push(entry_state(function.entry));
+    }
+    result
+}
+
+pub(crate) fn entry_state(entry: usize) -> SymbolicState {
+    let selector = Expr::apply(
+        Opcode::SHR,
+        vec![
+            Expr::Const(u256::new(224)),
+            Expr::Op(Opcode::CALLDATALOAD, vec![Expr::Const(u256::ZERO)]),
+        ],
+    );
+    SymbolicState::new(entry, vec![selector])
+}
+
+struct Dispatcher {
+    cfg: Cfg,
+    code: Vec<u8>,
+    /// The bucket blocks jumped to by each selector jump table.
+    tables: BTreeMap<usize, BTreeSet<usize>>,
+    /// Whether each entry stack item of each block holds the selector.
+    inputs: Vec<Vec<bool>>,
+    /// The statements defining each value, and whether they are selectors.
+    defs: BTreeMap<usize, (Statement, bool)>,
+}
+
+impl Dispatcher {
+    fn new(program: &Program) -> Self {
+        let cfg = build_cfg(program);
+        let mut code = Vec::new();
+        program.encode_into_vec(&mut code);
+        let mut dispatcher = Dispatcher {
+            inputs: cfg.depths.iter().map(|&depth| vec![false; depth]).collect(),
+            defs: BTreeMap::new(),
+            tables: BTreeMap::new(),
+            code,
+            cfg,
+        };
+        // An entry item holds the selector once every predecessor passes it,
+        // so blocks only reached through unresolved jumps never do.
+        let mut changed = true;
+        while changed {
+            changed = false;
+            for b in 0..dispatcher.cfg.blocks.len() {
+                dispatcher.define(b);
+                if !dispatcher.tables.contains_key(&b) {
+                    if let Some(targets) = dispatcher.table(b) {
+                        dispatcher.tables.insert(b, targets);
+                        changed = true;
+                    }
+                }
+            }
+            for s in 1..dispatcher.cfg.blocks.len() {
+                let predecessors: BTreeSet<usize> = dispatcher.cfg.predecessors[s]
+                    .iter()
+                    .copied()
+                    .chain(
+                        dispatcher
+                            .tables
+                            .iter()
+                            .filter(|(_, targets)| targets.contains(&s))
+                            .map(|(&b, _)| b),
+                    )
+                    .collect();
+                if predecessors.is_empty() {
+                    continue;
+                }
+                for slot in 0..dispatcher.inputs[s].len() {
+                    let selector = predecessors
+                        .iter()
+                        .all(|&p| dispatcher.is_selector(p, &dispatcher.cfg.exit(p, slot)));
+                    if selector && !dispatcher.inputs[s][slot] {
+                        dispatcher.inputs[s][slot] = true;
+                        changed = true;
+                    }
+                }
+            }
+        }
+        dispatcher
+    }
+
+    /// Returns the targets of a block ending in a jump to an entry of a
+    /// selector jump table, as `CODECOPY(32 - size, table + bucket * size,
+    /// size)` then `JUMP(MLOAD(0))` where the bucket is the selector modulo
+    /// the number of buckets.
+    fn table(&self, b: usize) -> Option<BTreeSet<usize>> {
+        let jump = self.cfg.lifted[b]
+            .terminator()
+            .filter(|stmt| stmt.opcode == Opcode::JUMP)?;
+        let load = self
+            .def(&jump.args[0])
+            .filter(|stmt| stmt.opcode == Opcode::MLOAD)?;
+        let word = load.args[0].constant()?;
+        let copy = self.cfg.lifted[b].statements.iter().find(|stmt| {
+            stmt.opcode == Opcode::CODECOPY
+                && matches!(
+                    (stmt.args[0].constant(), stmt.args[2].constant()),
+                    (Some(dest), Some(size)) if (u256::ONE..=u256::new(32)).contains(&size) && dest + size == word + 32
+                )
+        })?;
+        let size = copy.args[2].constant()?.as_usize();
+        let buckets = self.buckets(b, &copy.args[1])?;
+        let mut result = BTreeSet::new();
+        for bucket in 0..buckets {
+            let offset = usize::try_from(self.index(&copy.args[1], bucket)?).ok()?;
+            let entry = self.code.get(offset..offset.checked_add(size)?)?;
+            let pc = entry
+                .iter()
+                .fold(u256::ZERO, |pc, &byte| pc << 8 | byte as u128);
+            result.insert(self.cfg.jump_target(pc)?);
+        }
+        Some(result)
+    }
+
+    /// Returns the number of buckets of a `MOD` of the selector that a
+    /// table offset is computed from.
+    fn buckets(&self, b: usize, value: &Value) -> Option<usize> {
+        let stmt = self.def(value)?;
+        match (&stmt.opcode, stmt.args.as_slice()) {
+            (Opcode::MOD, [selector, Value::Const(n)]) if self.is_selector(b, selector) => {
+                usize::try_from(*n)
+                    .ok()
+                    .filter(|&n| n > 0 && n <= MAX_BUCKETS)
+            }
+            _ => stmt.args.iter().find_map(|arg| self.buckets(b, arg)),
+        }
+    }
+
+    /// Evaluates a table offset for the given bucket.
+    fn index(&self, value: &Value, bucket: usize) -> Option<u256> {
+        if let Value::Const(value) = value {
+            return Some(*value);
+        }
+        let stmt = self.def(value)?;
+        if stmt.opcode == Opcode::MOD {
+            return Some(u256::from(bucket as u64));
+        }
+        let args: Option<Vec<u256>> = stmt
+            .args
+            .iter()
+            .map(|arg| self.index(arg, bucket))
+            .collect();
+        eval_opcode(&stmt.opcode, &args?)
+    }
+
+    fn define(&mut self, b: usize) {
+        for stmt in &self.cfg.lifted[b].statements {
+            let Some(var) = stmt.result else {
+                continue;
+            };
+            let is_calldata = |value: &Value| {
+                self.def(value).is_some_and(|stmt| {
+                    stmt.opcode == Opcode::CALLDATALOAD && stmt.args[0] == Value::Const(u256::ZERO)
+                })
+            };
+            let selector = match (&stmt.opcode, stmt.args.as_slice()) {
+                (Opcode::SHR, [Value::Const(shift), value]) => *shift == 224 && is_calldata(value),
+                (Opcode::DIV, [value, Value::Const(divisor)]) => {
+                    *divisor == u256::ONE << 224 && is_calldata(value)
+                }
+                (Opcode::AND, [Value::Const(mask), value] | [value, Value::Const(mask)]) => {
+                    *mask == u256::from(u32::MAX) && self.is_selector(b, value)
+                }
+                _ => false,
+            };
+            self.defs.insert(var, (stmt.clone(), selector));
+        }
+    }
+
+    fn def(&self, value: &Value) -> Option<&Statement> {
+        match value {
+            Value::Var(var) => self.defs.get(var).map(|(stmt, _)| stmt),
+            _ => None,
+        }
+    }
+
+    fn is_selector(&self, b: usize, value: &Value) -> bool {
+        match value {
+            Value::Const(_) => false,
+            Value::Input(slot) => self.inputs[b].get(*slot).copied().unwrap_or(false),
+            Value::Var(var) => self.defs.get(var).is_some_and(|(_, selector)| *selector),
+        }
+    }
+
+    /// Strips any ISZERO from a jump condition, returning the underlying
+    /// value and whether the jump is taken when it is non-zero.
+    fn condition<'v>(&'v self, mut cond: &'v Value) -> (&'v Value, bool) {
+        let mut nonzero = true;
+        while let Some(stmt) = self.def(cond).filter(|stmt| stmt.opcode == Opcode::ISZERO) {
+            cond = &stmt.args[0];
+            nonzero = !nonzero;
+        }
+        (cond, nonzero)
+    }
+
+    /// Returns the selector a block's conditional jump compares against,
+    /// and whether the jump is taken when the selector matches.
+    fn comparison(&self, b: usize) -> Option<(u32, bool)> {
+        let stmt = self.cfg.lifted[b]
+            .terminator()
+            .filter(|stmt| stmt.opcode == Opcode::JUMPI)?;
+        let (cond, mut taken) = self.condition(&stmt.args[1]);
+        let stmt = self.def(cond)?;
+        let (constant, other) = match (&stmt.args[..], &stmt.opcode) {
+            (
+                [Value::Const(c), other] | [other, Value::Const(c)],
+                Opcode::EQ | Opcode::XOR | Opcode::SUB,
+            ) => (*c, other),
+            _ => return None,
+        };
+        if stmt.opcode != Opcode::EQ {
+            taken = !taken;
+        }
+        let selector = u32::try_from(constant).ok()?;
+        self.is_selector(b, other).then_some((selector, taken))
+    }
+
+    /// Returns the block at which the function compared against by this
+    /// block starts.
+    fn entry(&self, b: usize) -> Option<usize> {
+        let (_, taken) = self.comparison(b)?;
+        match taken {
+            true => self.target(b),
+            false => self.fallthrough(b),
+        }
+    }
+
+    fn target(&self, b: usize) -> Option<usize> {
+        let stmt = self.cfg.lifted[b].terminator()?;
+        self.cfg.jump_target(stmt.args[0].constant()?)
+    }
+
+    fn fallthrough(&self, b: usize) -> Option<usize> {
+        Some(b + 1).filter(|&next| self.cfg.successors[b].contains(&next))
+    }
+
+    fn reverts(&self, b: usize) -> bool {
+        matches!(
+            self.cfg.lifted[b].terminator().map(|stmt| &stmt.opcode),
+            Some(Opcode::REVERT | Opcode::INVALID)
+        )
+    }
+
+    /// Whether the code starting at this block reverts on a non-zero
+    /// CALLVALUE before branching on anything else.
+    fn rejects_value(&self, mut b: usize) -> bool {
+        for _ in 0..MAX_PROLOGUE {
+            let Some(stmt) = self.cfg.lifted[b].terminator() else {
+                match self.fallthrough(b) {
+                    Some(next) => b = next,
+                    None => return false,
+                }
+                continue;
+            };
+            match stmt.opcode {
+                Opcode::JUMP => match self.target(b) {
+                    Some(next) => b = next,
+                    None => return false,
+                },
+                Opcode::JUMPI => {
+                    let (cond, nonzero) = self.condition(&stmt.args[1]);
+                    match self.def(cond) {
+                        Some(stmt) if stmt.opcode == Opcode::CALLVALUE => {}
+                        _ => return false,
+                    }
+                    let branch = match nonzero {
+                        true => self.target(b),
+                        false => self.fallthrough(b),
+                    };
+                    return branch.is_some_and(|branch| self.reverts(branch));
+                }
+                _ => return false,
+            }
+        }
+        false
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{decode::decode_program, parse::parse_program};
+
+    fn selectors(input: &str) -> Vec<u32> {
+        let program = parse_program(input).unwrap();
+        extract_functions(&program)
+            .iter()
+            .map(|function| function.selector)
+            .collect()
+    }
+
+    #[test]
+    fn binary_search_dispatcher() {
+        let input = "PUSH1 0x04 CALLDATASIZE LT PUSH @fallback JUMPI
+            PUSH0 CALLDATALOAD PUSH1 0xe0 SHR
+            DUP1 PUSH4 0x80000000 GT PUSH @high JUMPI
+            DUP1 PUSH4 0x12345678 EQ PUSH @f JUMPI
+            fallback: JUMPDEST PUSH0 DUP1 REVERT
+            high: JUMPDEST DUP1 PUSH4 0xabcdef01 EQ PUSH @g JUMPI PUSH @fallback JUMP
+            f: JUMPDEST STOP
+            g: JUMPDEST STOP";
+        assert_eq!(selectors(input), vec![0x12345678, 0xabcdef01]);
+    }
+
+    #[test]
+    fn vyper_hash_table_dispatcher() {
+        let bytecode = [
+            // selector = CALLDATALOAD(0) >> 224, and the fallback for short calldata
+            "5f3560e01c",
+            "6004361060_1f57",
+            // CODECOPY(30, 0x5b + selector % 2 * 2, 2), JUMP(MLOAD(0))
+            "600281066001_1b605b01600290601e39_5f5156",
+            // 0x1f: the fallback
+            "5b5f80fd",
+            // 0x23, 0x29, 0x2f: the nonpayable functions
+            "5b3460_1f5700",
+            "5b3460_1f5700",
+            "5b3460_1f5700",
+            // 0x35: bucket 1
+            "5b63a9059cbb81146029_57601f56",
+            // 0x43: bucket 0
+            "5b631234567881146023_57",
+            "63abcdef00811460_2f57601f56",
+            // 0x5b: the bucket table
+            "00430035",
+        ]
+        .concat()
+        .replace('_', "");
+        let program = decode_program(&hex::decode(bytecode).unwrap()).unwrap();
+        let functions: Vec<(u32, usize)> = extract_functions(&program)
+            .iter()
+            .map(|function| (function.selector, function.entry))
+            .collect();
+        assert_eq!(
+            functions,
+            vec![(0x12345678, 0x23), (0xa9059cbb, 0x29), (0xabcdef00, 0x2f)]
+        );
+        assert!(extract_functions(&program).iter().all(|f| !f.payable));
+    }
+
+    #[test]
+    fn comparison_after_computed_jump() {
+        let input = "PUSH1 0x2a PUSH0 SLOAD JUMP
+            f: JUMPDEST PUSH1 0x05 EQ PUSH @g JUMPI STOP
+            g: JUMPDEST STOP";
+        assert!(selectors(input).is_empty());
+    }
+}
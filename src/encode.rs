@@ -1,5 +1,7 @@
 // This is free and unencumbered software released into the public domain.
 
+use alloc::vec::Vec;
+
 use crate::{opcode::Opcode, program::Program};
 
 pub fn encode_program(program: Program) -> Vec<u8> {
@@ -7,11 +9,10 @@ pub fn encode_program(program: Program) -> Vec<u8> {
 }
 
 pub fn encode_opcodes(opcodes: &[Opcode]) -> Vec<u8> {
-    let mut bytecode = vec![];
+    let mut bytecode = Vec::with_capacity(opcodes.iter().map(Opcode::size).sum());
     for opcode in opcodes.iter() {
         bytecode.push(encode_opcode(opcode));
-        let mut operands = encode_operands(opcode);
-        bytecode.append(&mut operands);
+        bytecode.extend_from_slice(immediate(opcode));
     }
     bytecode
 }
@@ -83,8 +84,7 @@ pub fn encode_opcode(opcode: &Opcode) -> u8 {
         MSIZE => 0x59,
         GAS => 0x5A,
         JUMPDEST => 0x5B,
-        PUSH1(_) => 0x60,
-        PUSHn(n, _, _) => 0x60 + n - 1,
+        PUSH(push) => 0x5F + push.width(),
         DUP(n) => 0x80 + n - 1,
         SWAP(n) => 0x90 + n - 1,
         LOG(n) => 0xA0 + n,
@@ -102,10 +102,12 @@ pub fn encode_opcode(opcode: &Opcode) -> u8 {
 }
 
 pub fn encode_operands(opcode: &Opcode) -> Vec<u8> {
-    use Opcode::*;
+    immediate(opcode).to_vec()
+}
+
+pub(crate) fn immediate(opcode: &Opcode) -> &[u8] {
     match opcode {
-        PUSH1(b) => vec![*b],
-        PUSHn(_, _, bs) => bs.clone(),
-        _ => vec![],
+        Opcode::PUSH(push) => push.bytes(),
+        _ => &[],
     }
 }
@@ -0,0 +1,228 @@
+// This is free and unencumbered software released into the public domain.
+
+use alloc::{collections::BTreeSet, vec::Vec};
+use core::fmt;
+
+use crate::{
+    dispatch::extract_functions,
+    event::event_topics,
+    program::Program,
+    signatures::{event_topic, function_selector},
+};
+
+#[derive(Clone, Copy, Debug, Hash, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Erc {
+    Erc20,
+    Erc165,
+    Erc721,
+    Erc1155,
+    Erc4626,
+}
+
+impl Erc {
+    pub const ALL: [Erc; 5] = [
+        Erc::Erc20,
+        Erc::Erc165,
+        Erc::Erc721,
+        Erc::Erc1155,
+        Erc::Erc4626,
+    ];
+
+    /// Returns the required members of the interface, as `function` and
+    /// `event` signatures. Optional members such as ERC-20's `name()` are
+    /// not included, nor are the ERC-20 members ERC-4626 inherits.
+    pub fn members(&self) -> &'static [&'static str] {
+        match self {
+            Erc::Erc20 => &[
+                "function totalSupply()",
+                "function balanceOf(address)",
+                "function transfer(address,uint256)",
+                "function transferFrom(address,address,uint256)",
+                "function approve(address,uint256)",
+                "function allowance(address,address)",
+                "event Transfer(address,address,uint256)",
+                "event Approval(address,address,uint256)",
+            ],
+            Erc::Erc165 => &["function supportsInterface(bytes4)"],
+            Erc::Erc721 => &[
+                "function balanceOf(address)",
+                "function ownerOf(uint256)",
+                "function safeTransferFrom(address,address,uint256,bytes)",
+                "function safeTransferFrom(address,address,uint256)",
+                "function transferFrom(address,address,uint256)",
+                "function approve(address,uint256)",
+                "function setApprovalForAll(address,bool)",
+                "function getApproved(uint256)",
+                "function isApprovedForAll(address,address)",
+                "function supportsInterface(bytes4)",
+                "event Transfer(address,address,uint256)",
+                "event Approval(address,address,uint256)",
+                "event ApprovalForAll(address,address,bool)",
+            ],
+            Erc::Erc1155 => &[
+                "function safeTransferFrom(address,address,uint256,uint256,bytes)",
+                "function safeBatchTransferFrom(address,address,uint256[],uint256[],bytes)",
+                "function balanceOf(address,uint256)",
+                "function balanceOfBatch(address[],uint256[])",
+                "function setApprovalForAll(address,bool)",
+                "function isApprovedForAll(address,address)",
+                "function supportsInterface(bytes4)",
+                "event TransferSingle(address,address,address,uint256,uint256)",
+                "event TransferBatch(address,address,address,uint256[],uint256[])",
+                "event ApprovalForAll(address,address,bool)",
+                "event URI(string,uint256)",
+            ],
+            Erc::Erc4626 => &[
+                "function asset()",
+                "function totalAssets()",
+                "function convertToShares(uint256)",
+                "function convertToAssets(uint256)",
+                "function maxDeposit(address)",
+                "function previewDeposit(uint256)",
+                "function deposit(uint256,address)",
+                "function maxMint(address)",
+                "function previewMint(uint256)",
+                "function mint(uint256,address)",
+                "function maxWithdraw(address)",
+                "function previewWithdraw(uint256)",
+                "function withdraw(uint256,address,address)",
+                "function maxRedeem(address)",
+                "function previewRedeem(uint256)",
+                "function redeem(uint256,address,address)",
+                "event Deposit(address,address,uint256,uint256)",
+                "event Withdraw(address,address,address,uint256,uint256)",
+            ],
+        }
+    }
+}
+
+impl fmt::Display for Erc {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let number = match self {
+            Erc::Erc20 => 20,
+            Erc::Erc165 => 165,
+            Erc::Erc721 => 721,
+            Erc::Erc1155 => 1155,
+            Erc::Erc4626 => 4626,
+        };
+        write!(f, "ERC-{}", number)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct InterfaceMatch {
+    pub standard: Erc,
+    /// The fraction of the interface's members found, from 0 to 1.
+    pub confidence: f64,
+    pub missing: Vec<&'static str>,
+}
+
+impl InterfaceMatch {
+    pub fn is_complete(&self) -> bool {
+        self.missing.is_empty()
+    }
+}
+
+/// Reports the ERC interfaces a program implements at least one member of,
+/// most complete first.
+///
+/// Functions are matched against the selectors in the program's dispatcher
+/// and events against the constant topics of its `LOG` instructions.
+pub fn detect_interfaces(program: &Program) -> Vec<InterfaceMatch> {
+    let selectors: BTreeSet<u32> = extract_functions(program)
+        .iter()
+        .map(|function| function.selector)
+        .collect();
+    let topics: BTreeSet<_> = event_topics(program).into_iter().collect();
+    let is_present = |member: &str| match member.split_once(' ') {
+        Some(("function", signature)) => selectors.contains(&function_selector(signature)),
+        Some(("event", signature)) => topics.contains(&event_topic(signature)),
+        _ => false,
+    };
+    let mut result: Vec<InterfaceMatch> = Erc::ALL
+        .iter()
+        .filter_map(|&standard| {
+            let members = standard.members();
+            let missing: Vec<&'static str> = members
+                .iter()
+                .copied()
+                .filter(|member| !is_present(member))
+                .collect();
+            (missing.len() < members.len()).then(|| InterfaceMatch {
+                standard,
+                confidence: (members.len() - missing.len()) as f64 / members.len() as f64,
+                missing,
+            })
+        })
+        .collect();
+    result.sort_by(|a, b| b.confidence.total_cmp(&a.confidence));
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse::parse_program;
+    use alloc::{
+        format,
+        string::{String, ToString},
+    };
+
+    const TRANSFER: &str = "Transfer(address,address,uint256)";
+    const APPROVAL: &str = "Approval(address,address,uint256)";
+
+    /// Assembles a dispatcher over the functions, each logging its event.
+    fn contract(functions: &[(&str, Option<&str>)]) -> Program {
+        let mut input = String::from("PUSH 0 CALLDATALOAD PUSH 0xe0 SHR\n");
+        for (i, (signature, _)) in functions.iter().enumerate() {
+            let selector = function_selector(signature);
+            input += &format!("DUP1 PUSH4 {:#010x} EQ PUSH @f{} JUMPI\n", selector, i);
+        }
+        input += "PUSH 0 DUP1 REVERT\n";
+        for (i, (_, event)) in functions.iter().enumerate() {
+            input += &format!("f{}: JUMPDEST\n", i);
+            if let Some(event) = event {
+                let topic = event_topic(event);
+                input += &format!("CALLER CALLER PUSH32 {:#x} PUSH 32 PUSH 0 LOG3\n", topic);
+            }
+            input += "STOP\n";
+        }
+        parse_program(&input).unwrap()
+    }
+
+    #[test]
+    fn erc20() {
+        let mut functions = [
+            ("totalSupply()", None),
+            ("balanceOf(address)", None),
+            ("transfer(address,uint256)", Some(TRANSFER)),
+            ("transferFrom(address,address,uint256)", Some(TRANSFER)),
+            ("approve(address,uint256)", Some(APPROVAL)),
+            ("allowance(address,address)", None),
+        ];
+        let matches = detect_interfaces(&contract(&functions));
+        assert_eq!(matches.len(), 2);
+        assert_eq!(matches[0].standard, Erc::Erc20);
+        assert!(matches[0].is_complete());
+        assert_eq!(matches[0].confidence, 1.0);
+        // ERC-721 shares three functions and both events.
+        assert_eq!(matches[1].standard, Erc::Erc721);
+        assert_eq!(matches[1].confidence, 5.0 / 13.0);
+        assert_eq!(matches[1].missing.len(), 8);
+
+        functions[4].1 = None;
+        let matches = detect_interfaces(&contract(&functions));
+        assert_eq!(matches[0].confidence, 7.0 / 8.0);
+        assert_eq!(
+            matches[0].missing,
+            ["event Approval(address,address,uint256)"]
+        );
+        assert_eq!(Erc::Erc20.to_string(), "ERC-20");
+    }
+
+    #[test]
+    fn no_interfaces() {
+        let program = contract(&[("foo()", None)]);
+        assert_eq!(detect_interfaces(&program), []);
+    }
+}
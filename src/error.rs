@@ -1,6 +1,7 @@
 // This is free and unencumbered software released into the public domain.
 
-use std::fmt;
+use alloc::string::String;
+use core::fmt;
 
 #[derive(Debug, Clone, PartialEq)]
 pub enum DecodeError {
@@ -20,3 +21,155 @@ impl fmt::Display for DecodeError {
         }
     }
 }
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum SsaError {
+    StackTooDeep(usize),
+    UnresolvedJump(usize),
+    CodeReference(usize),
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for SsaError {}
+
+impl fmt::Display for SsaError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        use SsaError::*;
+        match *self {
+            StackTooDeep(pc) => write!(f, "stack too deep in block 0x{:X}", pc),
+            UnresolvedJump(pc) => write!(f, "unresolved jump target at 0x{:X}", pc),
+            CodeReference(pc) => write!(f, "code layout read at 0x{:X}", pc),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum SignatureError {
+    InvalidLine(usize),
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for SignatureError {}
+
+impl fmt::Display for SignatureError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        use SignatureError::*;
+        match *self {
+            InvalidLine(line) => write!(f, "invalid signature on line {}", line),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ParseError {
+    UnknownOpcode(usize, usize),
+    InvalidLabel(usize, usize),
+    DuplicateLabel(usize, usize),
+    MissingImmediate(usize, usize),
+    InvalidImmediate(usize, usize),
+    UnexpectedImmediate(usize, usize),
+    UndefinedLabel(usize, usize),
+    LabelOutOfRange(usize, usize),
+    InvalidDirective(usize, usize),
+    UnterminatedMacro(usize, usize),
+    UndefinedMacro(usize, usize),
+    InvalidArguments(usize, usize),
+    UnresolvedInclude(usize, usize),
+    RecursionLimit(usize, usize),
+    Unsupported(usize, usize),
+    InvalidJson(usize, usize),
+}
+
+impl ParseError {
+    /// Returns the line and column of the error, both counting from 1.
+    pub fn position(&self) -> (usize, usize) {
+        use ParseError::*;
+        match *self {
+            UnknownOpcode(line, column)
+            | InvalidLabel(line, column)
+            | DuplicateLabel(line, column)
+            | MissingImmediate(line, column)
+            | InvalidImmediate(line, column)
+            | UnexpectedImmediate(line, column)
+            | UndefinedLabel(line, column)
+            | LabelOutOfRange(line, column)
+            | InvalidDirective(line, column)
+            | UnterminatedMacro(line, column)
+            | UndefinedMacro(line, column)
+            | InvalidArguments(line, column)
+            | UnresolvedInclude(line, column)
+            | RecursionLimit(line, column)
+            | Unsupported(line, column)
+            | InvalidJson(line, column) => (line, column),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for ParseError {}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        use ParseError::*;
+        let message = match *self {
+            UnknownOpcode(..) => "unknown opcode",
+            InvalidLabel(..) => "invalid label",
+            DuplicateLabel(..) => "duplicate label",
+            MissingImmediate(..) => "missing immediate",
+            InvalidImmediate(..) => "invalid immediate",
+            UnexpectedImmediate(..) => "unexpected immediate",
+            UndefinedLabel(..) => "undefined label",
+            LabelOutOfRange(..) => "label out of range",
+            InvalidDirective(..) => "invalid directive",
+            UnterminatedMacro(..) => "unterminated macro",
+            UndefinedMacro(..) => "undefined macro",
+            InvalidArguments(..) => "invalid arguments",
+            UnresolvedInclude(..) => "unresolved include",
+            RecursionLimit(..) => "expansion too deep",
+            Unsupported(..) => "unsupported construct",
+            InvalidJson(..) => "invalid JSON",
+        };
+        let (line, column) = self.position();
+        write!(f, "{} at line {}, column {}", message, line, column)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum BuildError {
+    InvalidLabel(String),
+    UndefinedLabel(String),
+    DuplicateLabel(String),
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for BuildError {}
+
+impl fmt::Display for BuildError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        use BuildError::*;
+        match self {
+            InvalidLabel(label) => write!(f, "invalid label {}", label),
+            UndefinedLabel(label) => write!(f, "undefined label {}", label),
+            DuplicateLabel(label) => write!(f, "duplicate label {}", label),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum PushError {
+    InvalidWidth(usize),
+    Overflow(u8),
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for PushError {}
+
+impl fmt::Display for PushError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        use PushError::*;
+        match *self {
+            InvalidWidth(width) => write!(f, "invalid PUSH width {}", width),
+            Overflow(width) => write!(f, "value does not fit in PUSH{}", width),
+        }
+    }
+}
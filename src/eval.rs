@@ -0,0 +1,222 @@
+// This is free and unencumbered software released into the public domain.
+
+use ethnum::u256;
+
+use crate::opcode::Opcode;
+
+/// Evaluates a pure opcode over concrete operands, topmost stack item first.
+///
+/// Returns `None` for opcodes whose result depends on anything beyond their
+/// operands (environment, memory, storage, control flow).
+pub fn eval_opcode(opcode: &Opcode, args: &[u256]) -> Option<u256> {
+    use Opcode::*;
+    if args.len() != opcode.stack_inputs() {
+        return None;
+    }
+    let bool = |b: bool| if b { u256::ONE } else { u256::ZERO };
+    let result = match opcode {
+        ADD => args[0].wrapping_add(args[1]),
+        MUL => args[0].wrapping_mul(args[1]),
+        SUB => args[0].wrapping_sub(args[1]),
+        DIV => args[0].checked_div(args[1]).unwrap_or(u256::ZERO),
+        SDIV => match args[1] {
+            u256::ZERO => u256::ZERO,
+            b => args[0].as_i256().wrapping_div(b.as_i256()).as_u256(),
+        },
+        MOD => args[0].checked_rem(args[1]).unwrap_or(u256::ZERO),
+        SMOD => match args[1] {
+            u256::ZERO => u256::ZERO,
+            b => args[0].as_i256().wrapping_rem(b.as_i256()).as_u256(),
+        },
+        ADDMOD => addmod(args[0], args[1], args[2]),
+        MULMOD => mulmod(args[0], args[1], args[2]),
+        EXP => exp(args[0], args[1]),
+        SIGNEXTEND => signextend(args[0], args[1]),
+        LT => bool(args[0] < args[1]),
+        GT => bool(args[0] > args[1]),
+        SLT => bool(args[0].as_i256() < args[1].as_i256()),
+        SGT => bool(args[0].as_i256() > args[1].as_i256()),
+        EQ => bool(args[0] == args[1]),
+        ISZERO => bool(args[0] == u256::ZERO),
+        AND => args[0] & args[1],
+        OR => args[0] | args[1],
+        XOR => args[0] ^ args[1],
+        NOT => !args[0],
+        BYTE => match args[0] {
+            i if i < 32 => (args[1] >> (248 - i.as_u32() * 8)) & 0xFF,
+            _ => u256::ZERO,
+        },
+        SHL => match args[0] {
+            n if n < 256 => args[1] << n.as_u32(),
+            _ => u256::ZERO,
+        },
+        SHR => match args[0] {
+            n if n < 256 => args[1] >> n.as_u32(),
+            _ => u256::ZERO,
+        },
+        SAR => {
+            let value = args[1].as_i256();
+            match args[0] {
+                n if n < 256 => (value >> n.as_u32()).as_u256(),
+                _ if value.is_negative() => u256::MAX,
+                _ => u256::ZERO,
+            }
+        }
+        _ => return None,
+    };
+    Some(result)
+}
+
+fn addmod(a: u256, b: u256, n: u256) -> u256 {
+    if n == u256::ZERO {
+        return u256::ZERO;
+    }
+    let (a, b) = (a % n, b % n);
+    match a.overflowing_add(b) {
+        (sum, true) => sum.wrapping_sub(n),
+        (sum, false) if sum >= n => sum - n,
+        (sum, false) => sum,
+    }
+}
+
+fn mulmod(a: u256, b: u256, n: u256) -> u256 {
+    if n == u256::ZERO {
+        return u256::ZERO;
+    }
+    let (mut a, mut b) = (a % n, b % n);
+    let mut result = u256::ZERO;
+    while b != u256::ZERO {
+        if b & 1 == 1 {
+            result = addmod(result, a, n);
+        }
+        a = addmod(a, a, n);
+        b >>= 1;
+    }
+    result
+}
+
+fn exp(base: u256, exponent: u256) -> u256 {
+    let (mut base, mut exponent) = (base, exponent);
+    let mut result = u256::ONE;
+    while exponent != u256::ZERO {
+        if exponent & 1 == 1 {
+            result = result.wrapping_mul(base);
+        }
+        base = base.wrapping_mul(base);
+        exponent >>= 1;
+    }
+    result
+}
+
+fn signextend(b: u256, x: u256) -> u256 {
+    if b >= 31 {
+        return x;
+    }
+    let bit = b.as_u32() * 8 + 7;
+    let mask = (u256::ONE << (bit + 1)) - 1;
+    if (x >> bit) & 1 == 1 {
+        x | !mask
+    } else {
+        x & mask
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn eval(opcode: Opcode, args: &[u256]) -> Option<u256> {
+        eval_opcode(&opcode, args)
+    }
+
+    fn neg(value: u32) -> u256 {
+        u256::ZERO.wrapping_sub(u256::from(value))
+    }
+
+    const MSB: u256 = u256::from_words(1 << 127, 0);
+
+    #[test]
+    fn shifts() {
+        use Opcode::*;
+        assert_eq!(eval(SHL, &[u256::ONE, u256::ONE]), Some(u256::new(2)));
+        assert_eq!(eval(SHL, &[u256::new(255), u256::ONE]), Some(MSB));
+        assert_eq!(eval(SHL, &[u256::new(256), u256::ONE]), Some(u256::ZERO));
+        assert_eq!(eval(SHR, &[u256::new(255), MSB]), Some(u256::ONE));
+        assert_eq!(eval(SAR, &[u256::new(4), neg(16)]), Some(neg(1)));
+        assert_eq!(eval(SAR, &[u256::ONE, MSB]), Some(MSB | MSB >> 1));
+        assert_eq!(eval(SAR, &[u256::new(256), neg(1)]), Some(u256::MAX));
+        assert_eq!(
+            eval(SAR, &[u256::new(256), u256::MAX >> 1]),
+            Some(u256::ZERO)
+        );
+    }
+
+    #[test]
+    fn bytes() {
+        use Opcode::*;
+        assert_eq!(
+            eval(BYTE, &[u256::new(31), u256::new(0x1234)]),
+            Some(u256::new(0x34))
+        );
+        assert_eq!(
+            eval(BYTE, &[u256::new(30), u256::new(0x1234)]),
+            Some(u256::new(0x12))
+        );
+        assert_eq!(eval(BYTE, &[u256::ZERO, MSB]), Some(u256::new(0x80)));
+        assert_eq!(eval(BYTE, &[u256::new(32), u256::MAX]), Some(u256::ZERO));
+        assert_eq!(
+            eval(SIGNEXTEND, &[u256::ZERO, u256::new(0xFF)]),
+            Some(u256::MAX)
+        );
+        assert_eq!(
+            eval(SIGNEXTEND, &[u256::ZERO, u256::new(0x17F)]),
+            Some(u256::new(0x7F))
+        );
+        assert_eq!(
+            eval(SIGNEXTEND, &[u256::ONE, u256::new(0x8000)]),
+            Some(neg(0x8000))
+        );
+        assert_eq!(
+            eval(SIGNEXTEND, &[u256::new(31), u256::new(0xFF)]),
+            Some(u256::new(0xFF))
+        );
+        assert_eq!(
+            eval(SIGNEXTEND, &[u256::MAX, u256::new(0xFF)]),
+            Some(u256::new(0xFF))
+        );
+    }
+
+    #[test]
+    fn modular() {
+        use Opcode::*;
+        // 2**256 is 1 modulo 3 and 4 modulo 12.
+        let max = u256::MAX;
+        assert_eq!(
+            eval(ADDMOD, &[max, u256::new(2), u256::new(3)]),
+            Some(u256::new(2))
+        );
+        assert_eq!(eval(ADDMOD, &[max, max, u256::new(12)]), Some(u256::new(6)));
+        assert_eq!(eval(MULMOD, &[max, max, u256::new(12)]), Some(u256::new(9)));
+        assert_eq!(
+            eval(MULMOD, &[u256::new(2), u256::new(3), u256::new(4)]),
+            Some(u256::new(2))
+        );
+        assert_eq!(
+            eval(ADDMOD, &[u256::ONE, u256::ONE, u256::ZERO]),
+            Some(u256::ZERO)
+        );
+        assert_eq!(
+            eval(MULMOD, &[u256::ONE, u256::ONE, u256::ZERO]),
+            Some(u256::ZERO)
+        );
+        assert_eq!(eval(SDIV, &[neg(6), u256::new(2)]), Some(neg(3)));
+        assert_eq!(eval(SMOD, &[neg(7), u256::new(3)]), Some(neg(1)));
+        assert_eq!(eval(EXP, &[u256::new(2), u256::new(256)]), Some(u256::ZERO));
+    }
+
+    #[test]
+    fn impure() {
+        assert_eq!(eval(Opcode::SLOAD, &[u256::ZERO]), None);
+        assert_eq!(eval(Opcode::ADD, &[u256::ONE]), None);
+    }
+}
@@ -0,0 +1,137 @@
+// This is free and unencumbered software released into the public domain.
+
+use alloc::{collections::BTreeSet, vec::Vec};
+use ethnum::u256;
+
+use crate::{
+    dispatch::entry_states,
+    opcode::Opcode,
+    program::Program,
+    symbolic::{explore_symbolic, Expr, SymbolicLimits},
+};
+
+/// An event emitted by a `LOG` instruction.
+#[derive(Clone, Copy, Debug, Hash, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Event {
+    pub pc: usize,
+    /// The first topic, which is the hash of the event's signature unless
+    /// the event is anonymous, or `None` if it is not a constant.
+    pub topic: Option<u256>,
+    /// The number of topics after the first, i.e. of indexed arguments.
+    pub indexed: usize,
+    /// The size of the non-indexed data in bytes, if it is a constant.
+    pub size: Option<usize>,
+}
+
+/// Extracts the events a program can emit, one per `LOG` site and topic.
+///
+/// Topics and sizes are resolved by executing the program symbolically,
+/// both from its start and from each function entry in its dispatcher.
+pub fn extract_events(program: &Program) -> Vec<Event> {
+    let limits = SymbolicLimits {
+        max_steps: 10_000,
+        max_paths: 256,
+    };
+    let mut result = BTreeSet::new();
+    for state in entry_states(program) {
+        for state in explore_symbolic(program, state, limits) {
+            for effect in &state.effects {
+                let Opcode::LOG(n) = effect.opcode else {
+                    continue;
+                };
+                result.insert(Event {
+                    pc: effect.pc,
+                    topic: effect.args.get(2).and_then(Expr::constant),
+                    indexed: (n as usize).saturating_sub(1),
+                    size: difference(&effect.args[1]).and_then(|size| usize::try_from(size).ok()),
+                });
+            }
+        }
+    }
+    let mut result: Vec<Event> = result.into_iter().collect();
+    // Drop unresolved topics at sites where some path resolved them.
+    let resolved: BTreeSet<usize> = result
+        .iter()
+        .filter(|event| event.topic.is_some())
+        .map(|event| event.pc)
+        .collect();
+    result.retain(|event| event.topic.is_some() || !resolved.contains(&event.pc));
+    result
+}
+
+/// Returns the distinct constant topics of the events a program can emit.
+pub fn event_topics(program: &Program) -> Vec<u256> {
+    let topics: BTreeSet<u256> = extract_events(program)
+        .iter()
+        .filter_map(|event| event.topic)
+        .collect();
+    topics.into_iter().collect()
+}
+
+/// Evaluates a size, folding differences between pointers with a common
+/// base such as `SUB(ADD(ptr, 0x40), ptr)`.
+fn difference(expr: &Expr) -> Option<u256> {
+    match expr {
+        Expr::Const(value) => Some(*value),
+        Expr::Op(Opcode::SUB, args) => {
+            let (a, x) = linear(&args[0]);
+            let (b, y) = linear(&args[1]);
+            (a == b).then(|| x.wrapping_sub(y))
+        }
+        _ => None,
+    }
+}
+
+/// Splits an expression into a base and a constant offset.
+fn linear(expr: &Expr) -> (&Expr, u256) {
+    match expr {
+        Expr::Op(Opcode::ADD, args) => match (&args[0], &args[1]) {
+            (Expr::Const(c), base) | (base, Expr::Const(c)) => {
+                let (base, offset) = linear(base);
+                (base, offset.wrapping_add(*c))
+            }
+            _ => (expr, u256::ZERO),
+        },
+        _ => (expr, u256::ZERO),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{parse::parse_program, signatures::event_topic};
+
+    #[test]
+    fn log_topics() {
+        // transfer(address,uint256) logs Transfer with its data at a pointer
+        // from calldata; the fallback logs anonymously.
+        let program = parse_program(
+            "PUSH 0 CALLDATALOAD PUSH 0xe0 SHR
+             DUP1 PUSH4 0xa9059cbb EQ PUSH @transfer JUMPI
+             PUSH 0 PUSH 0 LOG0
+             PUSH 0 CALLDATALOAD PUSH 0 PUSH 0 LOG1
+             PUSH 0 DUP1 REVERT
+             transfer: JUMPDEST
+             PUSH 0x24 CALLDATALOAD PUSH 4 CALLDATALOAD CALLER
+             PUSH32 0xddf252ad1be2c89b69c2b068fc378daa952ba7f163c4a11628f55a4df523b3ef
+             PUSH 0x44 CALLDATALOAD DUP1 PUSH 0x20 ADD DUP2 SWAP1 SUB SWAP1 LOG3 STOP",
+        )
+        .unwrap();
+        let topic = event_topic("Transfer(address,address,uint256)");
+        let event = |pc, topic, indexed, size| Event {
+            pc,
+            topic,
+            indexed,
+            size: Some(size),
+        };
+        assert_eq!(
+            extract_events(&program),
+            [
+                event(20, None, 0, 0),
+                event(28, None, 0, 0),
+                event(85, Some(topic), 2, 32),
+            ]
+        );
+        assert_eq!(event_topics(&program), [topic]);
+    }
+}
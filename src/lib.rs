@@ -1,15 +1,63 @@
 // This is free and unencumbered software released into the public domain.
 
+#![cfg_attr(not(feature = "std"), no_std)]
+
+extern crate alloc;
+
+mod abi;
+mod assemble;
+mod block;
+mod builder;
+mod cfg;
 mod decode;
+mod decompile;
+mod dialect;
+mod diff;
+mod disassemble;
+mod dispatch;
 mod encode;
+mod erc;
 mod error;
+mod eval;
+mod event;
+mod lift;
+mod lint;
 mod opcode;
 mod parse;
+mod preprocess;
 mod program;
+mod proxy;
+mod signatures;
+mod similarity;
+mod ssa;
+mod storage;
+mod symbolic;
 
+pub use crate::abi::*;
+pub use crate::assemble::*;
+pub use crate::block::*;
+pub use crate::builder::*;
+pub use crate::cfg::*;
 pub use crate::decode::*;
+pub use crate::decompile::*;
+pub use crate::dialect::*;
+pub use crate::diff::*;
+pub use crate::disassemble::*;
+pub use crate::dispatch::*;
 pub use crate::encode::*;
+pub use crate::erc::*;
 pub use crate::error::*;
+pub use crate::eval::*;
+pub use crate::event::*;
+pub use crate::lift::*;
+pub use crate::lint::*;
 pub use crate::opcode::*;
 pub use crate::parse::*;
+pub use crate::preprocess::*;
 pub use crate::program::*;
+pub use crate::proxy::*;
+pub use crate::signatures::*;
+pub use crate::similarity::*;
+pub use crate::ssa::*;
+pub use crate::storage::*;
+pub use crate::symbolic::*;
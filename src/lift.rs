@@ -0,0 +1,211 @@
+// This is free and unencumbered software released into the public domain.
+
+use alloc::{
+    string::{String, ToString},
+    vec::Vec,
+};
+use core::fmt;
+use ethnum::u256;
+
+use crate::{block::BasicBlock, eval::eval_opcode, opcode::Opcode, program::Program};
+
+#[derive(Clone, Copy, Debug, Hash, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Value {
+    Const(u256),
+    /// The stack item at this depth on entry to the block, 0 being the top.
+    Input(usize),
+    Var(usize),
+}
+
+impl Value {
+    pub fn constant(&self) -> Option<u256> {
+        match self {
+            Value::Const(value) => Some(*value),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Value::Const(value) => write!(f, "{:#x}", value),
+            Value::Input(n) => write!(f, "s{}", n),
+            Value::Var(n) => write!(f, "v{}", n),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Hash, PartialEq, Eq)]
+pub struct Statement {
+    pub pc: usize,
+    pub result: Option<usize>,
+    pub opcode: Opcode,
+    /// The operands, topmost stack item first.
+    pub args: Vec<Value>,
+}
+
+impl fmt::Display for Statement {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if let Some(result) = self.result {
+            write!(f, "{} = ", Value::Var(result))?;
+        }
+        write!(f, "{}", self.opcode)?;
+        if !self.args.is_empty() {
+            write!(f, "({})", join(&self.args))?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Clone, Debug, Hash, PartialEq, Eq)]
+pub struct LiftedBlock {
+    pub start: usize,
+    /// How many stack items the block consumes from its entry stack.
+    pub inputs: usize,
+    pub statements: Vec<Statement>,
+    /// The items replacing the consumed inputs on exit, topmost first.
+    pub outputs: Vec<Value>,
+}
+
+impl LiftedBlock {
+    pub fn terminator(&self) -> Option<&Statement> {
+        self.statements
+            .last()
+            .filter(|stmt| stmt.opcode.is_control())
+    }
+}
+
+impl fmt::Display for LiftedBlock {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let inputs: Vec<Value> = (0..self.inputs).map(Value::Input).collect();
+        writeln!(f, "block_{:#x}({}):", self.start, join(&inputs))?;
+        for statement in &self.statements {
+            writeln!(f, "    {}", statement)?;
+        }
+        writeln!(f, "    -> ({})", join(&self.outputs))
+    }
+}
+
+pub fn lift_program(program: &Program) -> Vec<LiftedBlock> {
+    let mut next_var = 0;
+    program
+        .basic_blocks()
+        .iter()
+        .map(|block| lift_block(block, &mut next_var))
+        .collect()
+}
+
+/// Lifts a basic block into three-address form, numbering the values it
+/// defines from `next_var` onwards.
+pub fn lift_block(block: &BasicBlock, next_var: &mut usize) -> LiftedBlock {
+    use Opcode::*;
+    let mut stack: Vec<Value> = Vec::new();
+    let mut inputs = 0;
+    let mut statements = Vec::new();
+    for (pc, op) in block.instructions() {
+        let needed = op.stack_inputs();
+        while stack.len() < needed {
+            stack.insert(0, Value::Input(inputs));
+            inputs += 1;
+        }
+        let len = stack.len();
+        match op {
+            PUSH(push) => stack.push(Value::Const(push.value())),
+            DUP(n) => stack.push(stack[len - *n as usize]),
+            SWAP(n) => stack.swap(len - 1, len - 1 - *n as usize),
+            POP => {
+                stack.pop();
+            }
+            JUMPDEST => {}
+            PC => stack.push(Value::Const(u256::from(pc as u64))),
+            _ => {
+                let args: Vec<Value> = (0..needed).map(|_| stack.pop().unwrap()).collect();
+                let values: Option<Vec<u256>> = args.iter().map(Value::constant).collect();
+                if let Some(value) = values.and_then(|values| eval_opcode(op, &values)) {
+                    stack.push(Value::Const(value));
+                    continue;
+                }
+                let result = match op.stack_outputs() {
+                    0 => None,
+                    _ => {
+                        stack.push(Value::Var(*next_var));
+                        *next_var += 1;
+                        Some(*next_var - 1)
+                    }
+                };
+                statements.push(Statement {
+                    pc,
+                    result,
+                    opcode: op.clone(),
+                    args,
+                });
+            }
+        }
+    }
+    LiftedBlock {
+        start: block.start,
+        inputs,
+        statements,
+        outputs: stack.into_iter().rev().collect(),
+    }
+}
+
+fn join(values: &[Value]) -> String {
+    values
+        .iter()
+        .map(Value::to_string)
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse::parse_program;
+    use alloc::format;
+
+    #[test]
+    fn three_address() {
+        let program = parse_program(
+            "PUSH 4 CALLDATALOAD PUSH 0 SLOAD ADD DUP1 PUSH 0 SSTORE PUSH @x JUMPI \
+             x: JUMPDEST PC PUSH 2 PUSH 3 MUL SWAP1 POP STOP",
+        )
+        .unwrap();
+        let blocks = lift_program(&program);
+        let text: String = blocks.iter().map(|block| format!("{}", block)).collect();
+        let expected = "block_0x0():
+    v0 = CALLDATALOAD(0x4)
+    v1 = SLOAD(0x0)
+    v2 = ADD(v1, v0)
+    SSTORE(0x0, v2)
+    JUMPI(0xe, v2)
+    -> ()
+block_0xe():
+    STOP
+    -> (0x6)
+";
+        assert_eq!(text, expected);
+        assert_eq!(blocks[0].terminator().map(|s| s.pc), Some(13));
+        assert_eq!(blocks[0].statements[2].args, [Value::Var(1), Value::Var(0)]);
+    }
+
+    #[test]
+    fn block_inputs() {
+        let program = parse_program("SWAP1 ADD DUP2").unwrap();
+        let block = &program.basic_blocks()[0];
+        let mut next_var = 7;
+        let lifted = lift_block(block, &mut next_var);
+        assert_eq!(lifted.inputs, 3);
+        assert_eq!(
+            lifted.statements[0].args,
+            [Value::Input(1), Value::Input(0)]
+        );
+        assert_eq!(
+            lifted.outputs,
+            [Value::Input(2), Value::Var(7), Value::Input(2)]
+        );
+        assert_eq!(next_var, 8);
+        assert_eq!(lifted.terminator(), None);
+    }
+}
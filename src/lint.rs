@@ -0,0 +1,287 @@
+// This is free and unencumbered software released into the public domain.
+
+use alloc::{
+    collections::{BTreeMap, BTreeSet},
+    vec,
+    vec::Vec,
+};
+use core::fmt;
+
+use crate::{
+    cfg::build_cfg,
+    dispatch::entry_states,
+    opcode::Opcode,
+    program::Program,
+    ssa::{build_ssa, Site, SsaProgram, SsaStatement},
+    symbolic::{explore_symbolic, Expr, Outcome, SymbolicLimits},
+};
+
+#[derive(Clone, Copy, Debug, Hash, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Severity {
+    Low,
+    Medium,
+    High,
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Severity::Low => write!(f, "low"),
+            Severity::Medium => write!(f, "medium"),
+            Severity::High => write!(f, "high"),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Hash, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Rule {
+    Selfdestruct,
+    ArbitraryDelegatecall,
+    UncheckedCall,
+    OriginAuth,
+    StoreAfterCall,
+    ArbitraryJump,
+    Callcode,
+}
+
+impl Rule {
+    pub fn severity(&self) -> Severity {
+        use Rule::*;
+        match self {
+            Selfdestruct | ArbitraryDelegatecall | ArbitraryJump => Severity::High,
+            UncheckedCall | OriginAuth | StoreAfterCall => Severity::Medium,
+            Callcode => Severity::Low,
+        }
+    }
+
+    pub fn explanation(&self) -> &'static str {
+        use Rule::*;
+        match self {
+            Selfdestruct => "SELFDESTRUCT is reachable, so the contract may be destroyed or drained",
+            ArbitraryDelegatecall => {
+                "DELEGATECALL target is taken from calldata, letting callers run arbitrary code in this contract's context"
+            }
+            UncheckedCall => "the success flag returned by this call is never used",
+            OriginAuth => {
+                "ORIGIN is compared against, which authenticates the transaction sender rather than the caller"
+            }
+            StoreAfterCall => {
+                "storage is written after an external CALL on the same path, which is prone to reentrancy"
+            }
+            ArbitraryJump => "jump target is taken from calldata",
+            Callcode => "CALLCODE is deprecated in favor of DELEGATECALL",
+        }
+    }
+}
+
+impl fmt::Display for Rule {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        use Rule::*;
+        let name = match self {
+            Selfdestruct => "selfdestruct",
+            ArbitraryDelegatecall => "arbitrary-delegatecall",
+            UncheckedCall => "unchecked-call",
+            OriginAuth => "origin-auth",
+            StoreAfterCall => "store-after-call",
+            ArbitraryJump => "arbitrary-jump",
+            Callcode => "callcode",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+#[derive(Clone, Copy, Debug, Hash, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Finding {
+    pub pc: usize,
+    pub rule: Rule,
+}
+
+impl Finding {
+    pub fn severity(&self) -> Severity {
+        self.rule.severity()
+    }
+
+    pub fn explanation(&self) -> &'static str {
+        self.rule.explanation()
+    }
+}
+
+impl fmt::Display for Finding {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{:#06x}: [{}] {}: {}",
+            self.pc,
+            self.severity(),
+            self.rule,
+            self.explanation()
+        )
+    }
+}
+
+/// Checks a program against every rule, returning the findings ordered by
+/// PC.
+pub fn lint_program(program: &Program) -> Vec<Finding> {
+    let mut result = BTreeSet::new();
+    let cfg = build_cfg(program);
+    let reachable = cfg.reachable();
+    for (block, _) in cfg.blocks.iter().zip(&reachable).filter(|(_, r)| **r) {
+        for (pc, op) in block.instructions() {
+            let rule = match op {
+                Opcode::SELFDESTRUCT => Rule::Selfdestruct,
+                Opcode::CALLCODE => Rule::Callcode,
+                _ => continue,
+            };
+            result.insert(Finding { pc, rule });
+        }
+    }
+    let ssa = build_ssa(program);
+    let uses = ssa.uses();
+    for block in &ssa.blocks {
+        for stmt in &block.statements {
+            let Some(var) = stmt.result else {
+                continue;
+            };
+            let rule = match stmt.opcode {
+                Opcode::CALL | Opcode::CALLCODE | Opcode::DELEGATECALL | Opcode::STATICCALL
+                    if !reaches(&ssa, &uses, var, &|_| true) =>
+                {
+                    Rule::UncheckedCall
+                }
+                Opcode::ORIGIN if reaches(&ssa, &uses, var, &|stmt| stmt.opcode == Opcode::EQ) => {
+                    Rule::OriginAuth
+                }
+                _ => continue,
+            };
+            result.insert(Finding { pc: stmt.pc, rule });
+        }
+    }
+    let limits = SymbolicLimits {
+        max_steps: 10_000,
+        max_paths: 256,
+    };
+    for initial in entry_states(program) {
+        for state in explore_symbolic(program, initial, limits) {
+            let mut called = false;
+            for effect in &state.effects {
+                match effect.opcode {
+                    Opcode::DELEGATECALL if from_calldata(&effect.args[1]) => {
+                        result.insert(Finding {
+                            pc: effect.pc,
+                            rule: Rule::ArbitraryDelegatecall,
+                        });
+                    }
+                    Opcode::CALL => called = true,
+                    Opcode::SSTORE if called => {
+                        result.insert(Finding {
+                            pc: effect.pc,
+                            rule: Rule::StoreAfterCall,
+                        });
+                    }
+                    _ => {}
+                }
+            }
+            if let Some(Outcome::UnresolvedJump(target)) = &state.outcome {
+                if from_calldata(target) {
+                    // The jump is the instruction before the halted PC.
+                    let pc = program
+                        .instructions()
+                        .take_while(|(pc, _)| *pc < state.pc)
+                        .last()
+                        .map_or(state.pc, |(pc, _)| pc);
+                    result.insert(Finding {
+                        pc,
+                        rule: Rule::ArbitraryJump,
+                    });
+                }
+            }
+        }
+    }
+    result.into_iter().collect()
+}
+
+/// Whether a variable flows, directly or through phis and masks, into a
+/// statement matching the predicate.
+fn reaches(
+    ssa: &SsaProgram,
+    uses: &BTreeMap<usize, Vec<Site>>,
+    var: usize,
+    predicate: &impl Fn(&SsaStatement) -> bool,
+) -> bool {
+    let mut pending = vec![var];
+    let mut visited = BTreeSet::new();
+    while let Some(var) = pending.pop() {
+        if !visited.insert(var) {
+            continue;
+        }
+        for site in uses.get(&var).into_iter().flatten() {
+            match *site {
+                Site::Phi(start, slot) => {
+                    let block = ssa.block(start).unwrap();
+                    pending.extend(
+                        block
+                            .phis
+                            .iter()
+                            .filter(|phi| phi.slot == slot)
+                            .map(|phi| phi.result),
+                    );
+                }
+                Site::Statement(start, index) => {
+                    let stmt = &ssa.block(start).unwrap().statements[index];
+                    if predicate(stmt) {
+                        return true;
+                    }
+                    if stmt.opcode == Opcode::AND {
+                        pending.extend(stmt.result);
+                    }
+                }
+            }
+        }
+    }
+    false
+}
+
+/// Whether an expression is a word of calldata, possibly masked or shifted,
+/// rather than a value looked up with it, such as from storage keyed by the
+/// selector as a diamond proxy does.
+fn from_calldata(expr: &Expr) -> bool {
+    match expr {
+        Expr::Op(Opcode::CALLDATALOAD, _) => true,
+        Expr::Op(Opcode::SLOAD, _) | Expr::Sha3(_) | Expr::Effect(..) | Expr::Const(_) => false,
+        Expr::Op(_, args) => args.iter().any(from_calldata),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse::parse_program;
+
+    fn rules(input: &str) -> Vec<Rule> {
+        let program = parse_program(input).unwrap();
+        lint_program(&program).into_iter().map(|f| f.rule).collect()
+    }
+
+    #[test]
+    fn delegatecall_to_calldata() {
+        let input = "PUSH1 0x04 CALLDATALOAD PUSH20 0xffffffffffffffffffffffffffffffffffffffff AND
+            PUSH0 PUSH0 CALLDATASIZE PUSH0 DUP5 GAS DELEGATECALL STOP";
+        assert!(rules(input).contains(&Rule::ArbitraryDelegatecall));
+    }
+
+    #[test]
+    fn delegatecall_to_diamond_facet() {
+        let input = "PUSH0 CALLDATALOAD PUSH1 0xe0 SHR PUSH0 MSTORE
+            PUSH32 0xc8fcad8db84d3cc18b4c41d551ea0ee66dd599cde068d998e57d5e09332c131c
+            PUSH1 0x20 MSTORE PUSH1 0x40 PUSH0 SHA3 SLOAD
+            PUSH0 PUSH0 CALLDATASIZE PUSH0 DUP5 GAS DELEGATECALL STOP";
+        assert!(!rules(input).contains(&Rule::ArbitraryDelegatecall));
+    }
+
+    #[test]
+    fn jump_to_storage_keyed_by_calldata() {
+        let input = "PUSH0 CALLDATALOAD PUSH0 MSTORE PUSH1 0x20 PUSH0 SHA3 SLOAD JUMP";
+        assert!(!rules(input).contains(&Rule::ArbitraryJump));
+        assert!(rules("PUSH0 CALLDATALOAD JUMP").contains(&Rule::ArbitraryJump));
+    }
+}
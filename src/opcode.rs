@@ -1,92 +1,154 @@
 // This is free and unencumbered software released into the public domain.
 
+use core::fmt;
 use ethnum::u256;
-use std::fmt;
+
+use crate::error::PushError;
+
+/// The immediate of a `PUSH0` to `PUSH32`, whose width is the number of
+/// bytes it pushes.
+#[derive(Clone, Copy, Debug, Hash, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Push {
+    width: u8,
+    /// The immediate bytes, followed by zeros.
+    bytes: [u8; 32],
+}
+
+impl Push {
+    /// Creates an immediate of the given width, failing if the value does
+    /// not fit in it.
+    pub fn new(width: u8, value: u256) -> Result<Push, PushError> {
+        if width > 32 {
+            return Err(PushError::InvalidWidth(width as usize));
+        }
+        if 256 - value.leading_zeros() > width as u32 * 8 {
+            return Err(PushError::Overflow(width));
+        }
+        Push::from_bytes(&value.to_be_bytes()[32 - width as usize..])
+    }
+
+    /// Creates an immediate from its bytes, failing if there are more than
+    /// 32.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Push, PushError> {
+        if bytes.len() > 32 {
+            return Err(PushError::InvalidWidth(bytes.len()));
+        }
+        let mut result = Push {
+            width: bytes.len() as u8,
+            bytes: [0; 32],
+        };
+        result.bytes[..bytes.len()].copy_from_slice(bytes);
+        Ok(result)
+    }
+
+    pub fn width(&self) -> u8 {
+        self.width
+    }
+
+    pub fn bytes(&self) -> &[u8] {
+        &self.bytes[..self.width as usize]
+    }
+
+    pub fn value(&self) -> u256 {
+        let mut word = [0; 32];
+        word[32 - self.width as usize..].copy_from_slice(self.bytes());
+        u256::from_be_bytes(word)
+    }
+}
 
 #[derive(Clone, Debug, Hash, PartialEq, Eq, PartialOrd, Ord)]
 pub enum Opcode {
-    STOP,                     // 0x00
-    ADD,                      // 0x01
-    MUL,                      // 0x02
-    SUB,                      // 0x03
-    DIV,                      // 0x04
-    SDIV,                     // 0x05
-    MOD,                      // 0x06
-    SMOD,                     // 0x07
-    ADDMOD,                   // 0x08
-    MULMOD,                   // 0x09
-    EXP,                      // 0x0A
-    SIGNEXTEND,               // 0x0B
-    LT,                       // 0x10
-    GT,                       // 0x11
-    SLT,                      // 0x12
-    SGT,                      // 0x13
-    EQ,                       // 0x14
-    ISZERO,                   // 0x15
-    AND,                      // 0x16
-    OR,                       // 0x17
-    XOR,                      // 0x18
-    NOT,                      // 0x19
-    BYTE,                     // 0x1A
-    SHL,                      // 0x1B (EIP-145)
-    SHR,                      // 0x1C (EIP-145)
-    SAR,                      // 0x1D (EIP-145)
-    SHA3,                     // 0x20
-    ADDRESS,                  // 0x30
-    BALANCE,                  // 0x31
-    ORIGIN,                   // 0x32
-    CALLER,                   // 0x33
-    CALLVALUE,                // 0x34
-    CALLDATALOAD,             // 0x35
-    CALLDATASIZE,             // 0x36
-    CALLDATACOPY,             // 0x37
-    CODESIZE,                 // 0x38
-    CODECOPY,                 // 0x39
-    GASPRICE,                 // 0x3A
-    EXTCODESIZE,              // 0x3B
-    EXTCODECOPY,              // 0x3C
-    RETURNDATASIZE,           // 0x3D (EIP-211)
-    RETURNDATACOPY,           // 0x3E (EIP-211)
-    EXTCODEHASH,              // 0x3F (EIP-1052)
-    BLOCKHASH,                // 0x40
-    COINBASE,                 // 0x41
-    TIMESTAMP,                // 0x42
-    NUMBER,                   // 0x43
-    DIFFICULTY,               // 0x44
-    GASLIMIT,                 // 0x45
-    CHAINID,                  // 0x46 (EIP-1344)
-    SELFBALANCE,              // 0x47 (EIP-1884)
-    BASEFEE,                  // 0x48
-    POP,                      // 0x50
-    MLOAD,                    // 0x51
-    MSTORE,                   // 0x52
-    MSTORE8,                  // 0x53
-    SLOAD,                    // 0x54
-    SSTORE,                   // 0x55
-    JUMP,                     // 0x56
-    JUMPI,                    // 0x57
-    PC,                       // 0x58
-    MSIZE,                    // 0x59
-    GAS,                      // 0x5A
-    JUMPDEST,                 // 0x5B
-    PUSH1(u8),                // 0x60
-    PUSHn(u8, u256, Vec<u8>), // 0x61..=0x7F
-    DUP(u8),                  // 0x80..=0x8F
-    SWAP(u8),                 // 0x90..=0x9F
-    LOG(u8),                  // 0xA0..=0xA4
-    CREATE,                   // 0xF0
-    CALL,                     // 0xF1
-    CALLCODE,                 // 0xF2
-    RETURN,                   // 0xF3
-    DELEGATECALL,             // 0xF4 (EIP-7)
-    CREATE2,                  // 0xF5 (EIP-1014)
-    STATICCALL,               // 0xFA
-    REVERT,                   // 0xFD (EIP-140)
-    INVALID,                  // 0xFE (EIP-141)
-    SELFDESTRUCT,             // 0xFF (EIP-6)
+    STOP,           // 0x00
+    ADD,            // 0x01
+    MUL,            // 0x02
+    SUB,            // 0x03
+    DIV,            // 0x04
+    SDIV,           // 0x05
+    MOD,            // 0x06
+    SMOD,           // 0x07
+    ADDMOD,         // 0x08
+    MULMOD,         // 0x09
+    EXP,            // 0x0A
+    SIGNEXTEND,     // 0x0B
+    LT,             // 0x10
+    GT,             // 0x11
+    SLT,            // 0x12
+    SGT,            // 0x13
+    EQ,             // 0x14
+    ISZERO,         // 0x15
+    AND,            // 0x16
+    OR,             // 0x17
+    XOR,            // 0x18
+    NOT,            // 0x19
+    BYTE,           // 0x1A
+    SHL,            // 0x1B (EIP-145)
+    SHR,            // 0x1C (EIP-145)
+    SAR,            // 0x1D (EIP-145)
+    SHA3,           // 0x20
+    ADDRESS,        // 0x30
+    BALANCE,        // 0x31
+    ORIGIN,         // 0x32
+    CALLER,         // 0x33
+    CALLVALUE,      // 0x34
+    CALLDATALOAD,   // 0x35
+    CALLDATASIZE,   // 0x36
+    CALLDATACOPY,   // 0x37
+    CODESIZE,       // 0x38
+    CODECOPY,       // 0x39
+    GASPRICE,       // 0x3A
+    EXTCODESIZE,    // 0x3B
+    EXTCODECOPY,    // 0x3C
+    RETURNDATASIZE, // 0x3D (EIP-211)
+    RETURNDATACOPY, // 0x3E (EIP-211)
+    EXTCODEHASH,    // 0x3F (EIP-1052)
+    BLOCKHASH,      // 0x40
+    COINBASE,       // 0x41
+    TIMESTAMP,      // 0x42
+    NUMBER,         // 0x43
+    DIFFICULTY,     // 0x44
+    GASLIMIT,       // 0x45
+    CHAINID,        // 0x46 (EIP-1344)
+    SELFBALANCE,    // 0x47 (EIP-1884)
+    BASEFEE,        // 0x48
+    POP,            // 0x50
+    MLOAD,          // 0x51
+    MSTORE,         // 0x52
+    MSTORE8,        // 0x53
+    SLOAD,          // 0x54
+    SSTORE,         // 0x55
+    JUMP,           // 0x56
+    JUMPI,          // 0x57
+    PC,             // 0x58
+    MSIZE,          // 0x59
+    GAS,            // 0x5A
+    JUMPDEST,       // 0x5B
+    PUSH(Push),     // 0x5F..=0x7F (EIP-3855)
+    DUP(u8),        // 0x80..=0x8F
+    SWAP(u8),       // 0x90..=0x9F
+    LOG(u8),        // 0xA0..=0xA4
+    CREATE,         // 0xF0
+    CALL,           // 0xF1
+    CALLCODE,       // 0xF2
+    RETURN,         // 0xF3
+    DELEGATECALL,   // 0xF4 (EIP-7)
+    CREATE2,        // 0xF5 (EIP-1014)
+    STATICCALL,     // 0xFA
+    REVERT,         // 0xFD (EIP-140)
+    INVALID,        // 0xFE (EIP-141)
+    SELFDESTRUCT,   // 0xFF (EIP-6)
 }
 
 impl Opcode {
+    /// Creates the smallest PUSH of a value, other than `PUSH0`.
+    pub fn push(value: u256) -> Opcode {
+        let width = (256 - value.leading_zeros()).div_ceil(8).max(1);
+        Opcode::PUSH(Push::new(width as u8, value).unwrap())
+    }
+
+    pub fn push_sized(width: u8, value: u256) -> Result<Opcode, PushError> {
+        Ok(Opcode::PUSH(Push::new(width, value)?))
+    }
+
     pub fn is_call(&self) -> bool {
         use Opcode::*;
         matches!(self, CALL | CALLCODE | DELEGATECALL | STATICCALL)
@@ -132,7 +194,7 @@ impl Opcode {
 
     pub fn is_one(&self) -> bool {
         use Opcode::*;
-        matches!(self, PUSH1(1) | PUSHn(_, u256::ONE, _))
+        matches!(self, PUSH(push) if push.value() == u256::ONE)
     }
 
     pub fn is_pop(&self) -> bool {
@@ -142,7 +204,7 @@ impl Opcode {
 
     pub fn is_push(&self) -> bool {
         use Opcode::*;
-        matches!(self, PUSH1(_) | PUSHn(_, _, _))
+        matches!(self, PUSH(_))
     }
 
     pub fn is_storage(&self) -> bool {
@@ -157,14 +219,49 @@ impl Opcode {
 
     pub fn is_zero(&self) -> bool {
         use Opcode::*;
-        matches!(self, PUSH1(0) | PUSHn(_, u256::ZERO, _))
+        matches!(self, PUSH(push) if push.value() == u256::ZERO)
     }
 
     pub fn size(&self) -> usize {
         use Opcode::*;
         match self {
-            PUSH1(_) => 1 + 1,
-            PUSHn(n, _, _) => 1 + *n as usize,
+            PUSH(push) => 1 + push.width() as usize,
+            _ => 1,
+        }
+    }
+
+    pub fn stack_inputs(&self) -> usize {
+        use Opcode::*;
+        match self {
+            STOP | INVALID | JUMPDEST => 0,
+            ADDRESS | ORIGIN | CALLER | CALLVALUE | CALLDATASIZE | CODESIZE | GASPRICE
+            | RETURNDATASIZE | COINBASE | TIMESTAMP | NUMBER | DIFFICULTY | GASLIMIT | CHAINID
+            | SELFBALANCE | BASEFEE | PC | MSIZE | GAS => 0,
+            PUSH(_) => 0,
+            ISZERO | NOT | BALANCE | CALLDATALOAD | EXTCODESIZE | EXTCODEHASH | BLOCKHASH | POP
+            | MLOAD | SLOAD | JUMP | SELFDESTRUCT => 1,
+            ADD | MUL | SUB | DIV | SDIV | MOD | SMOD | EXP | SIGNEXTEND | LT | GT | SLT | SGT
+            | EQ | AND | OR | XOR | BYTE | SHL | SHR | SAR | SHA3 => 2,
+            MSTORE | MSTORE8 | SSTORE | JUMPI | RETURN | REVERT => 2,
+            ADDMOD | MULMOD | CALLDATACOPY | CODECOPY | RETURNDATACOPY | CREATE => 3,
+            EXTCODECOPY | CREATE2 => 4,
+            DELEGATECALL | STATICCALL => 6,
+            CALL | CALLCODE => 7,
+            DUP(n) => *n as usize,
+            SWAP(n) => *n as usize + 1,
+            LOG(n) => *n as usize + 2,
+        }
+    }
+
+    pub fn stack_outputs(&self) -> usize {
+        use Opcode::*;
+        match self {
+            STOP | INVALID | JUMPDEST => 0,
+            CALLDATACOPY | CODECOPY | EXTCODECOPY | RETURNDATACOPY => 0,
+            POP | MSTORE | MSTORE8 | SSTORE | JUMP | JUMPI => 0,
+            RETURN | REVERT | SELFDESTRUCT | LOG(_) => 0,
+            DUP(n) => *n as usize + 1,
+            SWAP(n) => *n as usize + 1,
             _ => 1,
         }
     }
@@ -172,8 +269,7 @@ impl Opcode {
     pub fn zeroed(&self) -> Opcode {
         use Opcode::*;
         match self {
-            PUSH1(_) => PUSH1(0),
-            PUSHn(n, _, _) => PUSHn(*n, u256::ZERO, vec![0; *n as usize]),
+            PUSH(push) => PUSH(Push::new(push.width(), u256::ZERO).unwrap()),
             _ => self.clone(),
         }
     }
@@ -183,8 +279,8 @@ impl fmt::Display for Opcode {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         use Opcode::*;
         match self {
-            PUSH1(b) => write!(f, "PUSH1 0x{:02x}", b),
-            PUSHn(n, _, bs) => write!(f, "PUSH{} 0x{}", n, hex::encode(bs)),
+            PUSH(push) if push.width() == 0 => write!(f, "PUSH0"),
+            PUSH(push) => write!(f, "PUSH{} 0x{}", push.width(), hex::encode(push.bytes())),
             DUP(n) => write!(f, "DUP{}", n),
             SWAP(n) => write!(f, "SWAP{}", n),
             LOG(n) => write!(f, "LOG{}", n),
@@ -254,8 +350,7 @@ impl fmt::Display for Opcode {
                     MSIZE => "MSIZE",
                     GAS => "GAS",
                     JUMPDEST => "JUMPDEST",
-                    PUSH1(_) => unreachable!(),
-                    PUSHn(_, _, _) => unreachable!(),
+                    PUSH(_) => unreachable!(),
                     DUP(_) => unreachable!(),
                     SWAP(_) => unreachable!(),
                     LOG(_) => unreachable!(),
@@ -275,3 +370,58 @@ impl fmt::Display for Opcode {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::string::ToString;
+
+    #[test]
+    fn push_new() {
+        assert_eq!(Push::new(33, u256::ZERO), Err(PushError::InvalidWidth(33)));
+        assert_eq!(Push::new(0, u256::ONE), Err(PushError::Overflow(0)));
+        assert_eq!(Push::new(1, u256::new(0x100)), Err(PushError::Overflow(1)));
+        assert_eq!(Push::new(32, u256::MAX).unwrap().bytes(), &[0xff; 32]);
+        let push = Push::new(2, u256::new(0x12)).unwrap();
+        assert_eq!((push.width(), push.bytes()), (2, &[0x00, 0x12][..]));
+    }
+
+    #[test]
+    fn push_from_bytes() {
+        assert_eq!(Push::from_bytes(&[0; 33]), Err(PushError::InvalidWidth(33)));
+        let push = Push::from_bytes(&[]).unwrap();
+        assert_eq!((push.width(), push.value()), (0, u256::ZERO));
+        let push = Push::from_bytes(&[0x00, 0xab, 0xcd]).unwrap();
+        assert_eq!((push.width(), push.value()), (3, u256::new(0xabcd)));
+    }
+
+    #[test]
+    fn push_value() {
+        for width in 1..=32u8 {
+            // The largest value that fits, with every byte set.
+            let value = u256::MAX >> (256 - 8 * width as u32);
+            let push = Push::new(width, value).unwrap();
+            assert_eq!(push.bytes(), &[0xff; 32][..width as usize]);
+            assert_eq!(push.value(), value);
+            assert_eq!(Push::from_bytes(push.bytes()).unwrap(), push);
+        }
+    }
+
+    #[test]
+    fn push_opcode() {
+        assert_eq!(Opcode::push(u256::ZERO).size(), 2);
+        assert_eq!(Opcode::push(u256::new(0x100)).size(), 3);
+        assert_eq!(Opcode::push(u256::MAX).size(), 33);
+        assert_eq!(
+            Opcode::push_sized(1, u256::new(0x100)),
+            Err(PushError::Overflow(1))
+        );
+        assert_eq!(
+            Opcode::push_sized(0, u256::ZERO).unwrap().to_string(),
+            "PUSH0"
+        );
+        let op = Opcode::push_sized(4, u256::new(0xabcd)).unwrap();
+        assert_eq!(op.to_string(), "PUSH4 0x0000abcd");
+        assert_eq!(op.zeroed(), Opcode::push_sized(4, u256::ZERO).unwrap());
+    }
+}
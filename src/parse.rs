@@ -1,11 +1,185 @@
 // This is free and unencumbered software released into the public domain.
 
+use alloc::{
+    collections::BTreeSet,
+    string::{String, ToString},
+    vec::Vec,
+};
 use ethnum::u256;
 
-use crate::opcode::Opcode;
+use crate::{
+    assemble::assemble_statements,
+    error::ParseError,
+    opcode::Opcode,
+    preprocess::{preprocess, Include},
+    program::Program,
+};
+
+/// A statement of assembly text.
+#[derive(Clone, Debug, Hash, PartialEq, Eq)]
+pub enum AsmStatement {
+    /// A label definition, marking the position of the next instruction.
+    Label(String),
+    Opcode(Opcode),
+    /// A PUSH of a label's position, of the given width or else the
+    /// smallest that fits, with the line and column of the reference.
+    PushLabel {
+        width: Option<u8>,
+        label: String,
+        line: usize,
+        column: usize,
+    },
+}
+
+/// Parses assembly text into a program, with one or more instructions per
+/// line, e.g. `PUSH2 0x1234`, `PUSH1 42` or `PUSH @label`, `label:`
+/// definitions, and comments starting with `;` or `//`.
+///
+/// A bare `PUSH` takes the smallest width that fits its immediate. Lines
+/// may start with the PC and bytes columns of a disassembly listing, which
+/// are ignored. The text is first expanded by [`preprocess`], without
+/// includes.
+pub fn parse_program(input: &str) -> Result<Program, ParseError> {
+    parse_program_with(input, &mut |_, _| None)
+}
+
+/// Parses assembly text as [`parse_program`] does, reading included files
+/// with `include` as [`preprocess`] does.
+pub fn parse_program_with(input: &str, include: &mut Include<'_>) -> Result<Program, ParseError> {
+    let input = preprocess(input, include)?;
+    assemble_statements(&parse_statements(&input)?)
+}
+
+/// Parses assembly text into statements, as for [`parse_program`].
+pub fn parse_statements(input: &str) -> Result<Vec<AsmStatement>, ParseError> {
+    let mut result = Vec::new();
+    let mut labels = BTreeSet::new();
+    for (line, text) in input.lines().enumerate() {
+        let line = line + 1;
+        let mut tokens = tokens(text);
+        // Skip the PC and bytes columns of a disassembly listing.
+        if let [(_, pc), (_, bytes), ..] = tokens.as_slice() {
+            let is_hex = |s: &str| s.bytes().all(|b| b.is_ascii_hexdigit());
+            if pc.strip_prefix("0x").is_some_and(is_hex) && bytes.len() % 2 == 0 && is_hex(bytes) {
+                tokens.drain(..2);
+            }
+        }
+        let mut tokens = tokens.into_iter().peekable();
+        while let Some((column, token)) = tokens.next() {
+            if let Some(name) = token.strip_suffix(':') {
+                if !is_label(name) {
+                    return Err(ParseError::InvalidLabel(line, column));
+                }
+                if !labels.insert(name) {
+                    return Err(ParseError::DuplicateLabel(line, column));
+                }
+                result.push(AsmStatement::Label(name.to_string()));
+                continue;
+            }
+            let mnemonic = token.to_ascii_uppercase();
+            let width = match mnemonic.as_str() {
+                "PUSH" => None,
+                _ => {
+                    let op =
+                        parse_opcode(&mnemonic).ok_or(ParseError::UnknownOpcode(line, column))?;
+                    if op.size() == 1 {
+                        if let Some(&(column, token)) = tokens.peek() {
+                            if parse_immediate(token).is_some() || token.starts_with('@') {
+                                return Err(ParseError::UnexpectedImmediate(line, column));
+                            }
+                        }
+                        result.push(AsmStatement::Opcode(op));
+                        continue;
+                    }
+                    Some(op.size() as u8 - 1)
+                }
+            };
+            let (column, token) = tokens
+                .next()
+                .ok_or(ParseError::MissingImmediate(line, column))?;
+            if let Some(label) = token.strip_prefix('@') {
+                if !is_label(label) {
+                    return Err(ParseError::InvalidLabel(line, column));
+                }
+                result.push(AsmStatement::PushLabel {
+                    width,
+                    label: label.to_string(),
+                    line,
+                    column,
+                });
+                continue;
+            }
+            let value = parse_immediate(token).ok_or(ParseError::InvalidImmediate(line, column))?;
+            let op = match width {
+                None => Opcode::push(value),
+                Some(width) => Opcode::push_sized(width, value)
+                    .map_err(|_| ParseError::InvalidImmediate(line, column))?,
+            };
+            result.push(AsmStatement::Opcode(op));
+        }
+    }
+    Ok(result)
+}
+
+/// Splits a line into its tokens and their columns, dropping comments.
+pub(crate) fn tokens(line: &str) -> Vec<(usize, &str)> {
+    let code = strip_comment(line);
+    let end = code.len();
+    let mut result = Vec::new();
+    let mut start = None;
+    for (i, c) in code.char_indices().chain([(end, ' ')]) {
+        match (c.is_whitespace(), start) {
+            (true, Some(s)) => {
+                result.push((s + 1, &line[s..i]));
+                start = None;
+            }
+            (false, None) => start = Some(i),
+            _ => {}
+        }
+    }
+    result
+}
+
+/// Removes a `;` or `//` comment from a line.
+pub(crate) fn strip_comment(line: &str) -> &str {
+    let end = [line.find(';'), line.find("//")]
+        .into_iter()
+        .flatten()
+        .min()
+        .unwrap_or(line.len());
+    &line[..end]
+}
+
+pub(crate) fn is_label(name: &str) -> bool {
+    let mut chars = name.chars();
+    chars
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_' || c == '.')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '.')
+}
+
+/// Parses a hexadecimal (`0x`-prefixed) or decimal immediate.
+pub fn parse_immediate(input: &str) -> Option<u256> {
+    match input
+        .strip_prefix("0x")
+        .or_else(|| input.strip_prefix("0X"))
+    {
+        Some(digits) if !digits.is_empty() => u256::from_str_radix(digits, 16).ok(),
+        Some(_) => None,
+        None if input.bytes().all(|b| b.is_ascii_digit()) => u256::from_str_radix(input, 10).ok(),
+        None => None,
+    }
+}
 
 pub fn parse_opcode(input: &str) -> Option<Opcode> {
     use Opcode::*;
+    // Only plain decimal widths, without a sign or leading zeros.
+    let width = input.strip_prefix("PUSH").filter(|n| {
+        !n.is_empty() && n.bytes().all(|b| b.is_ascii_digit()) && (*n == "0" || !n.starts_with('0'))
+    });
+    if let Some(width) = width {
+        return Opcode::push_sized(width.parse().ok()?, u256::ZERO).ok();
+    }
     let opcode = match input {
         "STOP" => STOP,
         "ADD" => ADD,
@@ -33,7 +207,7 @@ pub fn parse_opcode(input: &str) -> Option<Opcode> {
         "SHL" => SHL,
         "SHR" => SHR,
         "SAR" => SAR,
-        "SHA3" => SHA3,
+        "SHA3" | "KECCAK256" => SHA3,
         "ADDRESS" => ADDRESS,
         "BALANCE" => BALANCE,
         "ORIGIN" => ORIGIN,
@@ -54,7 +228,7 @@ pub fn parse_opcode(input: &str) -> Option<Opcode> {
         "COINBASE" => COINBASE,
         "TIMESTAMP" => TIMESTAMP,
         "NUMBER" => NUMBER,
-        "DIFFICULTY" => DIFFICULTY,
+        "DIFFICULTY" | "PREVRANDAO" => DIFFICULTY,
         "GASLIMIT" => GASLIMIT,
         "CHAINID" => CHAINID,
         "SELFBALANCE" => SELFBALANCE,
@@ -71,38 +245,6 @@ pub fn parse_opcode(input: &str) -> Option<Opcode> {
         "MSIZE" => MSIZE,
         "GAS" => GAS,
         "JUMPDEST" => JUMPDEST,
-        "PUSH1" => PUSH1(0),
-        "PUSH2" => PUSHn(2, u256::ZERO, vec![0; 2]),
-        "PUSH3" => PUSHn(3, u256::ZERO, vec![0; 3]),
-        "PUSH4" => PUSHn(4, u256::ZERO, vec![0; 4]),
-        "PUSH5" => PUSHn(5, u256::ZERO, vec![0; 5]),
-        "PUSH6" => PUSHn(6, u256::ZERO, vec![0; 6]),
-        "PUSH7" => PUSHn(7, u256::ZERO, vec![0; 7]),
-        "PUSH8" => PUSHn(8, u256::ZERO, vec![0; 8]),
-        "PUSH9" => PUSHn(9, u256::ZERO, vec![0; 9]),
-        "PUSH10" => PUSHn(10, u256::ZERO, vec![0; 10]),
-        "PUSH11" => PUSHn(11, u256::ZERO, vec![0; 11]),
-        "PUSH12" => PUSHn(12, u256::ZERO, vec![0; 12]),
-        "PUSH13" => PUSHn(13, u256::ZERO, vec![0; 13]),
-        "PUSH14" => PUSHn(14, u256::ZERO, vec![0; 14]),
-        "PUSH15" => PUSHn(15, u256::ZERO, vec![0; 15]),
-        "PUSH16" => PUSHn(16, u256::ZERO, vec![0; 16]),
-        "PUSH17" => PUSHn(17, u256::ZERO, vec![0; 17]),
-        "PUSH18" => PUSHn(18, u256::ZERO, vec![0; 18]),
-        "PUSH19" => PUSHn(19, u256::ZERO, vec![0; 19]),
-        "PUSH20" => PUSHn(20, u256::ZERO, vec![0; 20]),
-        "PUSH21" => PUSHn(21, u256::ZERO, vec![0; 21]),
-        "PUSH22" => PUSHn(22, u256::ZERO, vec![0; 22]),
-        "PUSH23" => PUSHn(23, u256::ZERO, vec![0; 23]),
-        "PUSH24" => PUSHn(24, u256::ZERO, vec![0; 24]),
-        "PUSH25" => PUSHn(25, u256::ZERO, vec![0; 25]),
-        "PUSH26" => PUSHn(26, u256::ZERO, vec![0; 26]),
-        "PUSH27" => PUSHn(27, u256::ZERO, vec![0; 27]),
-        "PUSH28" => PUSHn(28, u256::ZERO, vec![0; 28]),
-        "PUSH29" => PUSHn(29, u256::ZERO, vec![0; 29]),
-        "PUSH30" => PUSHn(30, u256::ZERO, vec![0; 30]),
-        "PUSH31" => PUSHn(31, u256::ZERO, vec![0; 31]),
-        "PUSH32" => PUSHn(32, u256::ZERO, vec![0; 32]),
         "DUP1" => DUP(1),
         "DUP2" => DUP(2),
         "DUP3" => DUP(3),
@@ -154,3 +296,25 @@ pub fn parse_opcode(input: &str) -> Option<Opcode> {
     };
     Some(opcode)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn push_widths() {
+        assert_eq!(
+            parse_opcode("PUSH0"),
+            Opcode::push_sized(0, u256::ZERO).ok()
+        );
+        assert_eq!(
+            parse_opcode("PUSH32"),
+            Opcode::push_sized(32, u256::ZERO).ok()
+        );
+        for input in ["PUSH33", "PUSH+1", "PUSH01", "PUSH00", "PUSH-0", "PUSH 1"] {
+            assert_eq!(parse_opcode(input), None, "{}", input);
+        }
+        assert!(parse_program("PUSH+1 0x12").is_err());
+        assert!(parse_program("PUSH01 0x12").is_err());
+    }
+}
@@ -0,0 +1,365 @@
+// This is free and unencumbered software released into the public domain.
+
+use alloc::{
+    collections::BTreeMap,
+    format,
+    string::{String, ToString},
+    vec::Vec,
+};
+
+use crate::{
+    error::ParseError,
+    parse::{is_label, strip_comment},
+    signatures::{event_topic, function_selector},
+};
+
+/// Reads an included file, given the path of the including file and the
+/// path to include, returning the included file's own path and contents.
+pub type Include<'a> = dyn FnMut(&str, &str) -> Option<(String, String)> + 'a;
+
+/// The maximum nesting of macro expansions and includes.
+const MAX_DEPTH: usize = 64;
+
+/// Expands the directives and compile-time expressions in assembly text,
+/// reading included files with `include`, where the input itself has an
+/// empty path:
+///
+/// - `%define NAME text` replaces the word `NAME` with `text` from then on.
+/// - `%macro NAME a b` ... `%endmacro` defines a macro, expanded by
+///   `NAME(x, y)` with `<a>` and `<b>` in its body replaced by `x` and
+///   `y`. Labels defined in the body are local to each expansion.
+/// - `%include "path"` expands to the contents of another file, whose
+///   definitions remain visible after it.
+/// - `__FUNC_SIG("f(uint256)")` expands to the function's selector, and
+///   `__EVENT_HASH("E(uint256)")` to the event's topic.
+///
+/// The result has a line for each line of the input, so that positions in
+/// it point at the input line, with comments removed and any macro bodies
+/// and included files expanded onto the line that uses them. Errors in
+/// directives inside an included file are positioned within that file.
+pub fn preprocess(input: &str, include: &mut Include<'_>) -> Result<String, ParseError> {
+    let mut preprocessor = Preprocessor {
+        defines: BTreeMap::new(),
+        macros: BTreeMap::new(),
+        expansions: 0,
+        include,
+    };
+    Ok(preprocessor.run(input, "", 0)?.join("\n"))
+}
+
+struct Macro {
+    params: Vec<String>,
+    body: Vec<String>,
+}
+
+struct Preprocessor<'a> {
+    defines: BTreeMap<String, String>,
+    macros: BTreeMap<String, Macro>,
+    expansions: usize,
+    include: &'a mut Include<'a>,
+}
+
+impl Preprocessor<'_> {
+    fn run(&mut self, input: &str, file: &str, depth: usize) -> Result<Vec<String>, ParseError> {
+        let mut result = Vec::new();
+        let mut lines = input.lines().enumerate();
+        while let Some((line, text)) = lines.next() {
+            let line = line + 1;
+            let code = strip_comment(text);
+            let trimmed = code.trim_start();
+            let column = code.len() - trimmed.len() + 1;
+            let Some(directive) = trimmed.strip_prefix('%') else {
+                result.push(self.expand(code, line, depth)?);
+                continue;
+            };
+            let (name, rest) = directive
+                .split_once(char::is_whitespace)
+                .unwrap_or((directive, ""));
+            let invalid = ParseError::InvalidDirective(line, column);
+            match name {
+                "define" => {
+                    let (name, value) = rest
+                        .trim()
+                        .split_once(char::is_whitespace)
+                        .unwrap_or((rest.trim(), ""));
+                    if !is_label(name) {
+                        return Err(invalid);
+                    }
+                    let value = self.expand(value.trim(), line, depth)?;
+                    self.defines.insert(name.to_string(), value);
+                    result.push(String::new());
+                }
+                "macro" => {
+                    let mut words = rest.split_whitespace();
+                    let name = words.next().filter(|name| is_label(name)).ok_or(invalid)?;
+                    let params = words.map(String::from).collect();
+                    let mut body = Vec::new();
+                    result.push(String::new());
+                    loop {
+                        let (_, text) = lines
+                            .next()
+                            .ok_or(ParseError::UnterminatedMacro(line, column))?;
+                        result.push(String::new());
+                        let code = strip_comment(text);
+                        if code.trim() == "%endmacro" {
+                            break;
+                        }
+                        body.push(code.to_string());
+                    }
+                    self.macros.insert(name.to_string(), Macro { params, body });
+                }
+                "include" => {
+                    let path = unquote(rest.trim()).ok_or(invalid)?;
+                    if depth >= MAX_DEPTH {
+                        return Err(ParseError::RecursionLimit(line, column));
+                    }
+                    let (path, text) = (self.include)(file, path)
+                        .ok_or(ParseError::UnresolvedInclude(line, column))?;
+                    let lines = self.run(&text, &path, depth + 1)?;
+                    result.push(lines.join(" "));
+                }
+                _ => return Err(invalid),
+            }
+        }
+        Ok(result)
+    }
+
+    /// Expands the defines, macro calls and expressions in a line of code.
+    fn expand(&mut self, code: &str, line: usize, depth: usize) -> Result<String, ParseError> {
+        let mut result = String::new();
+        let mut rest = code;
+        while let Some(start) = rest.find(is_word) {
+            let (before, word) = rest.split_at(start);
+            let end = word.find(|c| !is_word(c)).unwrap_or(word.len());
+            let (word, after) = word.split_at(end);
+            result.push_str(before);
+            let column = code.len() - rest.len() + start + 1;
+            // Numbers such as `0xff` and labels are left alone.
+            if before.ends_with('@')
+                || word.starts_with(|c: char| c.is_ascii_digit())
+                || after.starts_with(':')
+            {
+                result.push_str(word);
+                rest = after;
+                continue;
+            }
+            let (args, after) = match after.strip_prefix('(') {
+                Some(after) if is_builtin(word) || self.macros.contains_key(word) => {
+                    arguments(after).ok_or(ParseError::InvalidArguments(line, column))?
+                }
+                Some(_) if !self.defines.contains_key(word) => {
+                    return Err(ParseError::UndefinedMacro(line, column));
+                }
+                _ => {
+                    result.push_str(self.defines.get(word).map_or(word, String::as_str));
+                    rest = after;
+                    continue;
+                }
+            };
+            rest = after;
+            let expansion = match word {
+                "__FUNC_SIG" | "__EVENT_HASH" => {
+                    let [signature] = args.as_slice() else {
+                        return Err(ParseError::InvalidArguments(line, column));
+                    };
+                    let signature = unquote(signature).unwrap_or(signature);
+                    match word {
+                        "__FUNC_SIG" => format!("{:#010x}", function_selector(signature)),
+                        _ => format!("{:#066x}", event_topic(signature)),
+                    }
+                }
+                _ => {
+                    if depth >= MAX_DEPTH {
+                        return Err(ParseError::RecursionLimit(line, column));
+                    }
+                    let body = self
+                        .instantiate(word, &args)
+                        .ok_or(ParseError::InvalidArguments(line, column))?;
+                    self.expand(&body, line, depth + 1)?
+                }
+            };
+            result.push_str(&expansion);
+        }
+        result.push_str(rest);
+        Ok(result)
+    }
+
+    /// Substitutes the arguments into a macro's body, on a single line, and
+    /// renames the labels defined in it.
+    fn instantiate(&mut self, name: &str, args: &[&str]) -> Option<String> {
+        let definition = self.macros.get(name)?;
+        if definition.params.len() != args.len() && !(definition.params.is_empty() && args == [""])
+        {
+            return None;
+        }
+        let mut body = definition.body.join(" ");
+        for (param, arg) in definition.params.iter().zip(args) {
+            body = body.replace(&format!("<{}>", param), arg);
+        }
+        self.expansions += 1;
+        let locals: Vec<&str> = body
+            .split_whitespace()
+            .filter_map(|token| token.strip_suffix(':'))
+            .collect();
+        let renamed = body
+            .split_whitespace()
+            .map(|token| {
+                let (prefix, label, suffix) =
+                    match (token.strip_prefix('@'), token.strip_suffix(':')) {
+                        (Some(label), _) => ("@", label, ""),
+                        (_, Some(label)) => ("", label, ":"),
+                        _ => return token.to_string(),
+                    };
+                match locals.contains(&label) {
+                    true => format!("{}{}.{}{}", prefix, label, self.expansions, suffix),
+                    false => token.to_string(),
+                }
+            })
+            .collect::<Vec<_>>()
+            .join(" ");
+        Some(renamed)
+    }
+}
+
+fn is_builtin(word: &str) -> bool {
+    matches!(word, "__FUNC_SIG" | "__EVENT_HASH")
+}
+
+fn is_word(c: char) -> bool {
+    c.is_ascii_alphanumeric() || c == '_' || c == '.'
+}
+
+/// Splits the arguments of a call up to its closing parenthesis, returning
+/// them and the text after it.
+fn arguments(input: &str) -> Option<(Vec<&str>, &str)> {
+    let mut result = Vec::new();
+    let (mut depth, mut quoted, mut start) = (0, false, 0);
+    for (i, c) in input.char_indices() {
+        match c {
+            '"' => quoted = !quoted,
+            '(' if !quoted => depth += 1,
+            ')' if !quoted && depth > 0 => depth -= 1,
+            ')' if !quoted => {
+                result.push(input[start..i].trim());
+                return Some((result, &input[i + 1..]));
+            }
+            ',' if !quoted && depth == 0 => {
+                result.push(input[start..i].trim());
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    None
+}
+
+fn unquote(input: &str) -> Option<&str> {
+    input.strip_prefix('"')?.strip_suffix('"')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn expand(input: &str) -> Result<String, ParseError> {
+        preprocess(input, &mut |_, _| None)
+    }
+
+    #[test]
+    fn defines() {
+        assert_eq!(
+            expand("%define X 0x10\n%define Y X\nPUSH1 Y\nX: PUSH @X ; X"),
+            Ok("\n\nPUSH1 0x10\nX: PUSH @X ".into())
+        );
+    }
+
+    #[test]
+    fn macros() {
+        let input = "%macro STORE slot v
+                     PUSH1 <v> PUSH1 <slot> SSTORE
+                     loop: JUMPDEST PUSH @loop JUMP
+                     %endmacro
+                     STORE(0, 1)
+                     STORE(2, 3)";
+        let output = expand(input).unwrap();
+        let lines: Vec<&str> = output.lines().map(str::trim).collect();
+        assert_eq!(
+            lines,
+            [
+                "",
+                "",
+                "",
+                "",
+                "PUSH1 1 PUSH1 0 SSTORE loop.1: JUMPDEST PUSH @loop.1 JUMP",
+                "PUSH1 3 PUSH1 2 SSTORE loop.2: JUMPDEST PUSH @loop.2 JUMP",
+            ]
+        );
+    }
+
+    #[test]
+    fn builtins() {
+        assert_eq!(
+            expand(
+                "PUSH4 __FUNC_SIG(\"transfer(address,uint256)\")\n\
+                 PUSH32 __EVENT_HASH(Transfer(address,address,uint256))"
+            ),
+            Ok("PUSH4 0xa9059cbb\n\
+                PUSH32 0xddf252ad1be2c89b69c2b068fc378daa952ba7f163c4a11628f55a4df523b3ef"
+                .into())
+        );
+    }
+
+    #[test]
+    fn includes() {
+        let files = [
+            ("lib/a.asm", "%define ONE 1\n%include \"b.asm\""),
+            ("lib/b.asm", "PUSH1 ONE"),
+        ];
+        let mut requests = Vec::new();
+        let mut include = |from: &str, path: &str| {
+            requests.push((from.to_string(), path.to_string()));
+            let directory = from.rsplit_once('/').map_or("", |(directory, _)| directory);
+            let path = match directory {
+                "" => path.to_string(),
+                _ => format!("{}/{}", directory, path),
+            };
+            let (_, text) = files.iter().find(|(name, _)| *name == path)?;
+            Some((path, text.to_string()))
+        };
+        let output = preprocess("%include \"lib/a.asm\"\nPUSH1 ONE", &mut include);
+        assert_eq!(output, Ok(" PUSH1 1\nPUSH1 1".into()));
+        assert_eq!(
+            requests,
+            [
+                (String::new(), "lib/a.asm".to_string()),
+                ("lib/a.asm".to_string(), "b.asm".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn errors() {
+        use ParseError::*;
+        let mut recursive = |_: &str, _: &str| Some(("a.asm".into(), "%include \"a.asm\"".into()));
+        assert_eq!(
+            preprocess("PUSH1 1\n  %include \"a.asm\"", &mut recursive),
+            Err(RecursionLimit(1, 1))
+        );
+        let cases = [
+            ("PUSH1 1\nFOO(1, 2)", UndefinedMacro(2, 1)),
+            ("%macro M\nM()\n%endmacro\nM()", RecursionLimit(4, 1)),
+            (
+                "%macro M a\n<a>\n%endmacro\nPUSH1 M(1, 2)",
+                InvalidArguments(4, 7),
+            ),
+            ("%macro M\nSTOP", UnterminatedMacro(1, 1)),
+            ("%include \"a.asm\"", UnresolvedInclude(1, 1)),
+            ("%include a.asm", InvalidDirective(1, 1)),
+            (" %undef X", InvalidDirective(1, 2)),
+            ("%define 1 2", InvalidDirective(1, 1)),
+        ];
+        for (input, error) in cases {
+            assert_eq!(expand(input), Err(error), "{}", input);
+        }
+    }
+}
@@ -1,13 +1,74 @@
 // This is free and unencumbered software released into the public domain.
 
-use std::{collections::BTreeSet, iter, slice};
+use alloc::{collections::BTreeSet, vec::Vec};
+use core::{iter, slice};
+#[cfg(feature = "std")]
+use std::io;
 
-use crate::opcode::Opcode;
+use crate::{
+    block::BasicBlock,
+    encode::{encode_opcode, immediate},
+    opcode::Opcode,
+};
 
 #[derive(Clone, Debug, Hash, PartialEq, Eq, PartialOrd, Ord)]
 pub struct Program(pub Vec<Opcode>);
 
 impl Program {
+    pub fn basic_blocks(&self) -> Vec<BasicBlock> {
+        let mut result = Vec::new();
+        let mut current: Option<BasicBlock> = None;
+        for (pc, op) in self.instructions() {
+            if op.is_jumpdest() {
+                result.extend(current.take());
+            }
+            let block = current.get_or_insert_with(|| BasicBlock::new(pc));
+            block.opcodes.push(op.clone());
+            if op.is_control() {
+                result.extend(current.take());
+            }
+        }
+        result.extend(current);
+        result
+    }
+
+    pub fn instructions(&self) -> impl Iterator<Item = (usize, &Opcode)> {
+        self.0.iter().scan(0, |pc, op| {
+            let result = (*pc, op);
+            *pc += op.size();
+            Some(result)
+        })
+    }
+
+    /// Returns the size of the program's bytecode.
+    pub fn encoded_len(&self) -> usize {
+        self.0.iter().map(Opcode::size).sum()
+    }
+
+    /// Appends the program's bytecode to a buffer, reserving its size
+    /// up front.
+    pub fn encode_into_vec(&self, output: &mut Vec<u8>) {
+        output.reserve(self.encoded_len());
+        for op in &self.0 {
+            output.push(encode_opcode(op));
+            output.extend_from_slice(immediate(op));
+        }
+    }
+
+    /// Writes the program's bytecode, one instruction at a time, without
+    /// buffering it.
+    #[cfg(feature = "std")]
+    pub fn encode_into(&self, output: &mut impl io::Write) -> io::Result<()> {
+        let mut buffer = [0; 33];
+        for op in &self.0 {
+            let operands = immediate(op);
+            buffer[0] = encode_opcode(op);
+            buffer[1..=operands.len()].copy_from_slice(operands);
+            output.write_all(&buffer[..op.size()])?;
+        }
+        Ok(())
+    }
+
     pub fn opcode_set(&self) -> BTreeSet<Opcode> {
         let mut result = BTreeSet::new();
         for op in &self.0 {
@@ -27,3 +88,49 @@ impl<'a> iter::IntoIterator for &'a Program {
         self.0.iter().cloned()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{decode::decode_program, encode::encode_program};
+    use alloc::vec;
+    use ethnum::u256;
+
+    fn program() -> Program {
+        Program(vec![
+            Opcode::push_sized(0, u256::ZERO).unwrap(),
+            Opcode::push(u256::new(0x80)),
+            Opcode::push_sized(32, u256::MAX).unwrap(),
+            Opcode::push_sized(4, u256::new(0xa9059cbb)).unwrap(),
+            Opcode::DUP(16),
+            Opcode::SSTORE,
+            Opcode::LOG(4),
+            Opcode::STOP,
+        ])
+    }
+
+    #[test]
+    fn encoding() {
+        let program = program();
+        let expected = encode_program(program.clone());
+        assert_eq!(program.encoded_len(), expected.len());
+        assert_eq!(program.encoded_len(), 1 + 2 + 33 + 5 + 4);
+        assert_eq!(expected[..3], [0x5f, 0x60, 0x80]);
+        assert_eq!(decode_program(&expected), Ok(program.clone()));
+
+        let mut buffer = vec![0xfe];
+        program.encode_into_vec(&mut buffer);
+        assert_eq!(buffer[0], 0xfe);
+        assert_eq!(buffer[1..], expected);
+        assert_eq!(Program(Vec::new()).encoded_len(), 0);
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn streaming() {
+        let program = program();
+        let mut output = Vec::new();
+        program.encode_into(&mut output).unwrap();
+        assert_eq!(output, encode_program(program));
+    }
+}
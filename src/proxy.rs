@@ -0,0 +1,276 @@
+// This is free and unencumbered software released into the public domain.
+
+use ethnum::{u256, U256};
+
+use crate::{
+    dispatch::extract_functions,
+    opcode::Opcode,
+    program::Program,
+    signatures::function_selector,
+    symbolic::{execute_symbolic, Expr, SymbolicLimits},
+};
+
+/// `bytes32(uint256(keccak256("eip1967.proxy.implementation")) - 1)`
+pub const EIP1967_IMPLEMENTATION_SLOT: u256 = U256::from_words(
+    0x360894a13ba1a3210667c828492db98d,
+    0xca3e2076cc3735a920a3ca505d382bbc,
+);
+
+/// `bytes32(uint256(keccak256("eip1967.proxy.admin")) - 1)`
+pub const EIP1967_ADMIN_SLOT: u256 = U256::from_words(
+    0xb53127684a568b3173ae13b9f8a6016e,
+    0x243e63b6e8ee1178d6a717850b5d6103,
+);
+
+/// `bytes32(uint256(keccak256("eip1967.proxy.beacon")) - 1)`
+pub const EIP1967_BEACON_SLOT: u256 = U256::from_words(
+    0xa3f0ad74e5423aebfd80d3ef43465783,
+    0x35a9a72aeaee59ff6cb3582b35133d50,
+);
+
+/// `keccak256("diamond.standard.diamond.storage")`
+pub const DIAMOND_STORAGE_POSITION: u256 = U256::from_words(
+    0xc8fcad8db84d3cc18b4c41d551ea0ee6,
+    0x6dd599cde068d998e57d5e09332c131c,
+);
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ProxyKind {
+    /// An EIP-1167 minimal proxy, with its implementation address.
+    Minimal([u8; 20]),
+    /// An EIP-1967 proxy that also reads the admin slot, i.e. a transparent
+    /// proxy.
+    Transparent,
+    /// An EIP-1967 proxy reading only the implementation slot, as used with
+    /// UUPS implementations.
+    Eip1967,
+    /// A UUPS implementation, which exposes `proxiableUUID()` and performs
+    /// its own upgrades.
+    Uups,
+    /// An EIP-1967 beacon proxy, which asks the beacon for its
+    /// implementation.
+    Beacon,
+    /// An EIP-2535 diamond, which looks up the facet for each selector.
+    Diamond,
+    /// Some other proxy forwarding its calldata with `DELEGATECALL` to this
+    /// target.
+    Forwarding(Expr),
+}
+
+/// Recognizes the proxy pattern a program implements, if any.
+pub fn detect_proxy(program: &Program) -> Option<ProxyKind> {
+    if let Some(implementation) = minimal_proxy(program) {
+        return Some(ProxyKind::Minimal(implementation));
+    }
+    let uups = function_selector("proxiableUUID()");
+    if extract_functions(program)
+        .iter()
+        .any(|function| function.selector == uups)
+    {
+        return Some(ProxyKind::Uups);
+    }
+    let limits = SymbolicLimits {
+        max_steps: 10_000,
+        max_paths: 256,
+    };
+    let states = execute_symbolic(program, limits);
+    let reads = |slot: u256| {
+        states.iter().any(|state| {
+            let effects = state.effects.iter().flat_map(|effect| &effect.args);
+            state
+                .constraints
+                .iter()
+                .chain(effects)
+                .any(|expr| expr.contains(&|expr| is_sload(expr, slot)))
+        })
+    };
+    let mut result = None;
+    for state in &states {
+        for effect in &state.effects {
+            if effect.opcode != Opcode::DELEGATECALL || !forwards_calldata(&effect.args[3]) {
+                continue;
+            }
+            let target = &effect.args[1];
+            let kind = if target.contains(&is_facet_lookup) {
+                ProxyKind::Diamond
+            } else if reads(EIP1967_BEACON_SLOT) {
+                ProxyKind::Beacon
+            } else if target.contains(&|expr| is_sload(expr, EIP1967_IMPLEMENTATION_SLOT)) {
+                match reads(EIP1967_ADMIN_SLOT) {
+                    true => ProxyKind::Transparent,
+                    false => ProxyKind::Eip1967,
+                }
+            } else {
+                ProxyKind::Forwarding(target.clone())
+            };
+            // Prefer the more specific kinds over plain forwarding.
+            match (&result, &kind) {
+                (None, _) | (Some(ProxyKind::Forwarding(_)), _) => result = Some(kind),
+                _ => {}
+            }
+        }
+    }
+    result
+}
+
+/// Matches the EIP-1167 runtime code, allowing for the shorter pushes of
+/// vanity implementation addresses, and returns the implementation.
+fn minimal_proxy(program: &Program) -> Option<[u8; 20]> {
+    use Opcode::*;
+    let prefix = [
+        CALLDATASIZE,
+        RETURNDATASIZE,
+        RETURNDATASIZE,
+        CALLDATACOPY,
+        RETURNDATASIZE,
+        RETURNDATASIZE,
+        RETURNDATASIZE,
+        CALLDATASIZE,
+        RETURNDATASIZE,
+    ];
+    let suffix = [
+        GAS,
+        DELEGATECALL,
+        RETURNDATASIZE,
+        DUP(3),
+        DUP(1),
+        RETURNDATACOPY,
+        SWAP(1),
+        RETURNDATASIZE,
+        SWAP(2),
+        Opcode::push(u256::ZERO),
+        JUMPI,
+        REVERT,
+        JUMPDEST,
+        RETURN,
+    ];
+    let code = &program.0;
+    if code.len() < prefix.len() + 1 + suffix.len() || code[..prefix.len()] != prefix {
+        return None;
+    }
+    let implementation = match &code[prefix.len()] {
+        PUSH(push) if push.width() <= 20 => push.value(),
+        _ => return None,
+    };
+    let rest = &code[prefix.len() + 1..prefix.len() + 1 + suffix.len()];
+    if !rest.iter().map(Opcode::zeroed).eq(suffix) {
+        return None;
+    }
+    let mut result = [0; 20];
+    result.copy_from_slice(&implementation.to_be_bytes()[12..]);
+    Some(result)
+}
+
+fn forwards_calldata(size: &Expr) -> bool {
+    size.contains(&|expr| expr.is_op(&Opcode::CALLDATASIZE))
+}
+
+fn is_sload(expr: &Expr, slot: u256) -> bool {
+    match expr {
+        Expr::Op(Opcode::SLOAD, args) | Expr::Effect(_, Opcode::SLOAD, args) => {
+            args[0] == Expr::Const(slot)
+        }
+        _ => false,
+    }
+}
+
+/// Whether an expression loads a mapping entry keyed by the calldata
+/// selector, as in `ds.facets[msg.sig]`.
+fn is_facet_lookup(expr: &Expr) -> bool {
+    match expr {
+        Expr::Op(Opcode::SLOAD, args) | Expr::Effect(_, Opcode::SLOAD, args) => {
+            args[0].contains(&|expr| match expr {
+                Expr::Sha3(words) => {
+                    words.iter().any(|word| {
+                        word.contains(&|expr| {
+                            expr.is_op(&Opcode::CALLDATALOAD)
+                                && expr.args()[0] == Expr::Const(u256::ZERO)
+                        })
+                    }) || words.contains(&Expr::Const(DIAMOND_STORAGE_POSITION))
+                }
+                _ => false,
+            })
+        }
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{decode::decode_program, parse::parse_program, signatures::keccak256};
+    use alloc::{format, vec};
+
+    fn slot(name: &str) -> u256 {
+        u256::from_be_bytes(keccak256(name.as_bytes()))
+    }
+
+    fn decode(code: &str) -> Program {
+        decode_program(&hex::decode(code).unwrap()).unwrap()
+    }
+
+    /// Assembles a proxy delegating its calldata to `target`, after `prelude`.
+    fn forwarder(prelude: &str, target: &str) -> Program {
+        parse_program(&format!(
+            "{} CALLDATASIZE PUSH 0 DUP1 CALLDATACOPY
+             PUSH 0 PUSH 0 CALLDATASIZE PUSH 0 {} GAS DELEGATECALL STOP",
+            prelude, target
+        ))
+        .unwrap()
+    }
+
+    #[test]
+    fn slots() {
+        let implementation = slot("eip1967.proxy.implementation") - 1;
+        assert_eq!(EIP1967_IMPLEMENTATION_SLOT, implementation);
+        assert_eq!(EIP1967_ADMIN_SLOT, slot("eip1967.proxy.admin") - 1);
+        assert_eq!(EIP1967_BEACON_SLOT, slot("eip1967.proxy.beacon") - 1);
+        assert_eq!(
+            DIAMOND_STORAGE_POSITION,
+            slot("diamond.standard.diamond.storage")
+        );
+    }
+
+    #[test]
+    fn minimal() {
+        let code = "363d3d373d3d3d363d73bebebebebebebebebebebebebebebebebebebebe\
+                    5af43d82803e903d91602b57fd5bf3";
+        assert_eq!(
+            detect_proxy(&decode(code)),
+            Some(ProxyKind::Minimal([0xBE; 20]))
+        );
+        // A vanity address with leading zero bytes takes a shorter PUSH.
+        let code = format!(
+            "363d3d373d3d3d363d71{}5af43d82803e903d91602957fd5bf3",
+            "be".repeat(18)
+        );
+        let mut address = [0xBE; 20];
+        address[..2].fill(0);
+        assert_eq!(
+            detect_proxy(&decode(&code)),
+            Some(ProxyKind::Minimal(address))
+        );
+        assert_eq!(detect_proxy(&decode(&code.replace("5af4", "5af1"))), None);
+    }
+
+    #[test]
+    fn eip1967() {
+        let implementation = format!("PUSH32 {:#x} SLOAD", EIP1967_IMPLEMENTATION_SLOT);
+        let program = forwarder("", &implementation);
+        assert_eq!(detect_proxy(&program), Some(ProxyKind::Eip1967));
+        let admin = format!(
+            "PUSH32 {:#x} SLOAD CALLER EQ PUSH @admin JUMPI",
+            EIP1967_ADMIN_SLOT
+        );
+        let program = forwarder(&admin, &format!("{} admin: JUMPDEST", implementation));
+        assert_eq!(detect_proxy(&program), Some(ProxyKind::Transparent));
+    }
+
+    #[test]
+    fn forwarding() {
+        let program = forwarder("", "PUSH 0 SLOAD");
+        let target = Expr::Op(Opcode::SLOAD, vec![Expr::Const(u256::ZERO)]);
+        assert_eq!(detect_proxy(&program), Some(ProxyKind::Forwarding(target)));
+        assert_eq!(detect_proxy(&parse_program("STOP").unwrap()), None);
+    }
+}
@@ -0,0 +1,266 @@
+// This is free and unencumbered software released into the public domain.
+
+use alloc::{collections::BTreeMap, format, string::String, vec::Vec};
+use ethnum::u256;
+#[cfg(feature = "std")]
+use std::{fs, io, path::Path};
+use tiny_keccak::{Hasher, Keccak};
+
+use crate::{error::SignatureError, opcode::Opcode, program::Program};
+
+const BUILTIN: &str = include_str!("signatures.txt");
+
+/// A database mapping function selectors and event topics to their text
+/// signatures, such as `transfer(address,uint256)`.
+///
+/// Databases are plain text, with one entry per line in one of the forms
+/// `function <signature>`, `event <signature>`, or `0x<hash> <signature>`,
+/// where a 4-byte hash is a selector and a 32-byte hash an event topic.
+/// Empty lines and lines starting with `#` are ignored.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Signatures {
+    functions: BTreeMap<u32, Vec<String>>,
+    events: BTreeMap<u256, Vec<String>>,
+}
+
+impl Signatures {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the database of common signatures embedded in this crate.
+    pub fn builtin() -> Self {
+        Self::parse(BUILTIN).expect("valid builtin signatures")
+    }
+
+    #[cfg(feature = "std")]
+    pub fn load(path: impl AsRef<Path>) -> io::Result<Self> {
+        let mut result = Self::new();
+        result.extend_from_file(path)?;
+        Ok(result)
+    }
+
+    pub fn parse(text: &str) -> Result<Self, SignatureError> {
+        let mut result = Self::new();
+        result.extend_from_str(text)?;
+        Ok(result)
+    }
+
+    #[cfg(feature = "std")]
+    pub fn extend_from_file(&mut self, path: impl AsRef<Path>) -> io::Result<()> {
+        let text = fs::read_to_string(path)?;
+        self.extend_from_str(&text)
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err.to_string()))
+    }
+
+    pub fn extend_from_str(&mut self, text: &str) -> Result<(), SignatureError> {
+        for (index, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let invalid = SignatureError::InvalidLine(index + 1);
+            let (kind, signature) = line
+                .split_once(char::is_whitespace)
+                .ok_or(invalid.clone())?;
+            let signature = signature.trim();
+            if !is_signature(signature) {
+                return Err(invalid);
+            }
+            match kind {
+                "function" => {
+                    self.insert_function(signature);
+                }
+                "event" => {
+                    self.insert_event(signature);
+                }
+                _ => {
+                    let hash = kind
+                        .strip_prefix("0x")
+                        .and_then(|hash| hex::decode(hash).ok())
+                        .ok_or(invalid.clone())?;
+                    match hash.len() {
+                        4 => insert(
+                            &mut self.functions,
+                            u32::from_be_bytes(hash.try_into().unwrap()),
+                            signature,
+                        ),
+                        32 => insert(
+                            &mut self.events,
+                            u256::from_be_bytes(hash.try_into().unwrap()),
+                            signature,
+                        ),
+                        _ => return Err(invalid),
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Adds a function signature, returning its selector.
+    pub fn insert_function(&mut self, signature: &str) -> u32 {
+        let selector = function_selector(signature);
+        insert(&mut self.functions, selector, signature);
+        selector
+    }
+
+    /// Adds an event signature, returning its topic.
+    pub fn insert_event(&mut self, signature: &str) -> u256 {
+        let topic = event_topic(signature);
+        insert(&mut self.events, topic, signature);
+        topic
+    }
+
+    /// Returns the first known signature for a function selector.
+    pub fn function(&self, selector: u32) -> Option<&str> {
+        self.functions(selector).first().map(String::as_str)
+    }
+
+    /// Returns all known signatures for a function selector, of which there
+    /// may be several due to collisions.
+    pub fn functions(&self, selector: u32) -> &[String] {
+        self.functions.get(&selector).map_or(&[], Vec::as_slice)
+    }
+
+    /// Returns the first known signature for an event topic.
+    pub fn event(&self, topic: u256) -> Option<&str> {
+        self.events(topic).first().map(String::as_str)
+    }
+
+    pub fn events(&self, topic: u256) -> &[String] {
+        self.events.get(&topic).map_or(&[], Vec::as_slice)
+    }
+
+    /// Returns the known signature an immediate value may stand for, given
+    /// the PUSH instruction it appears in.
+    pub fn lookup(&self, opcode: &Opcode) -> Option<&str> {
+        match opcode {
+            Opcode::PUSH(push) => match push.width() {
+                3 | 4 => self.function(push.value().as_u32()),
+                32 => self.event(push.value()),
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+
+    /// Disassembles a program one instruction per line, annotating known
+    /// selectors and topics with their signatures, e.g.
+    /// `0x000f: PUSH4 0xa9059cbb // transfer(address,uint256)`.
+    pub fn annotate(&self, program: &Program) -> String {
+        let mut result = String::new();
+        for (pc, opcode) in program.instructions() {
+            result.push_str(&format!("{:#06x}: {}", pc, opcode));
+            if let Some(signature) = self.lookup(opcode) {
+                result.push_str(&format!(" // {}", signature));
+            }
+            result.push('\n');
+        }
+        result
+    }
+}
+
+pub fn keccak256(data: &[u8]) -> [u8; 32] {
+    let mut hasher = Keccak::v256();
+    let mut output = [0; 32];
+    hasher.update(data);
+    hasher.finalize(&mut output);
+    output
+}
+
+/// Returns the 4-byte selector of a function signature.
+pub fn function_selector(signature: &str) -> u32 {
+    let hash = keccak256(signature.as_bytes());
+    u32::from_be_bytes([hash[0], hash[1], hash[2], hash[3]])
+}
+
+/// Returns the topic of an event signature.
+pub fn event_topic(signature: &str) -> u256 {
+    u256::from_be_bytes(keccak256(signature.as_bytes()))
+}
+
+fn is_signature(signature: &str) -> bool {
+    match signature.split_once('(') {
+        Some((name, _)) => {
+            !name.is_empty() && signature.ends_with(')') && !signature.contains(char::is_whitespace)
+        }
+        None => false,
+    }
+}
+
+fn insert<K: Ord>(map: &mut BTreeMap<K, Vec<String>>, key: K, signature: &str) {
+    let signatures = map.entry(key).or_default();
+    if !signatures.iter().any(|s| s == signature) {
+        signatures.push(signature.into());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse::parse_program;
+
+    const TRANSFER_TOPIC: &str = "ddf252ad1be2c89b69c2b068fc378daa952ba7f163c4a11628f55a4df523b3ef";
+
+    #[test]
+    fn hashes() {
+        assert_eq!(function_selector("transfer(address,uint256)"), 0xa9059cbb);
+        assert_eq!(
+            event_topic("Transfer(address,address,uint256)"),
+            u256::from_str_radix(TRANSFER_TOPIC, 16).unwrap()
+        );
+    }
+
+    #[test]
+    fn builtin_lookup() {
+        let signatures = Signatures::builtin();
+        assert_eq!(
+            signatures.function(0xa9059cbb),
+            Some("transfer(address,uint256)")
+        );
+        let topic = u256::from_str_radix(TRANSFER_TOPIC, 16).unwrap();
+        assert_eq!(
+            signatures.event(topic),
+            Some("Transfer(address,address,uint256)")
+        );
+        assert_eq!(signatures.function(0), None);
+    }
+
+    #[test]
+    fn parsing() {
+        let text = "# comment\n\nfunction transfer(address,uint256)\n0xa9059cbb other()\n\
+                    0xa9059cbb transfer(address,uint256)\nevent Ping()";
+        let signatures = Signatures::parse(text).unwrap();
+        assert_eq!(
+            signatures.functions(0xa9059cbb),
+            ["transfer(address,uint256)", "other()"]
+        );
+        assert_eq!(signatures.events(event_topic("Ping()")), ["Ping()"]);
+        for (text, line) in [
+            ("function transfer", 1),
+            ("\nfunction f( )", 2),
+            ("0x1234 f()", 1),
+            ("class f()", 1),
+            ("function", 1),
+        ] {
+            assert_eq!(
+                Signatures::parse(text).unwrap_err(),
+                SignatureError::InvalidLine(line),
+                "{}",
+                text
+            );
+        }
+    }
+
+    #[test]
+    fn annotation() {
+        let program = parse_program("PUSH4 0xa9059cbb PUSH2 0xa905 STOP").unwrap();
+        assert_eq!(
+            Signatures::builtin().annotate(&program),
+            "0x0000: PUSH4 0xa9059cbb // transfer(address,uint256)\n\
+             0x0005: PUSH2 0xa905\n\
+             0x0008: STOP\n"
+        );
+    }
+}
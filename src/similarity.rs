@@ -0,0 +1,223 @@
+// This is free and unencumbered software released into the public domain.
+
+use alloc::{
+    collections::{BTreeMap, BTreeSet},
+    vec,
+    vec::Vec,
+};
+
+use crate::{
+    cfg::build_cfg, diff::function_bodies, encode::encode_opcode, opcode::Opcode, program::Program,
+};
+
+/// The length of the opcode n-grams that are hashed.
+const NGRAM: usize = 4;
+
+/// The number of hash functions in a MinHash signature.
+const PERMUTATIONS: usize = 64;
+
+/// A fingerprint of a program's code that ignores PUSH immediates and
+/// widths, so it is unaffected by constants, immutables and jump offsets,
+/// and unreachable code, so it is unaffected by appended metadata.
+#[derive(Clone, Debug, Hash, PartialEq, Eq)]
+pub struct Fingerprint {
+    /// A hash of the normalized code of each function, by selector.
+    pub functions: BTreeMap<u32, u64>,
+    /// The MinHash signature of the code's opcode n-grams.
+    pub minhash: [u64; PERMUTATIONS],
+    /// The SimHash of the code's opcode n-grams.
+    pub simhash: u64,
+}
+
+impl Fingerprint {
+    /// Estimates the Jaccard similarity of the two programs' n-gram sets.
+    pub fn minhash_similarity(&self, other: &Fingerprint) -> f64 {
+        let equal = self
+            .minhash
+            .iter()
+            .zip(&other.minhash)
+            .filter(|(a, b)| a == b)
+            .count();
+        equal as f64 / PERMUTATIONS as f64
+    }
+
+    /// Returns the number of bits in which the two SimHashes differ.
+    pub fn simhash_distance(&self, other: &Fingerprint) -> u32 {
+        (self.simhash ^ other.simhash).count_ones()
+    }
+
+    /// Returns the fraction of functions of either program that are
+    /// identical in both, or `None` if neither has a dispatcher.
+    pub fn function_similarity(&self, other: &Fingerprint) -> Option<f64> {
+        let selectors: BTreeSet<&u32> = self
+            .functions
+            .keys()
+            .chain(other.functions.keys())
+            .collect();
+        if selectors.is_empty() {
+            return None;
+        }
+        let equal = selectors
+            .iter()
+            .filter(|selector| {
+                self.functions
+                    .get(selector)
+                    .is_some_and(|hash| other.functions.get(selector) == Some(hash))
+            })
+            .count();
+        Some(equal as f64 / selectors.len() as f64)
+    }
+
+    /// Scores the similarity of two programs from 0 to 1, averaging the
+    /// MinHash, SimHash and function similarities.
+    pub fn similarity(&self, other: &Fingerprint) -> f64 {
+        let mut scores = vec![
+            self.minhash_similarity(other),
+            1.0 - self.simhash_distance(other) as f64 / 64.0,
+        ];
+        scores.extend(self.function_similarity(other));
+        scores.iter().sum::<f64>() / scores.len() as f64
+    }
+}
+
+/// Computes the fingerprint of a program.
+pub fn fingerprint(program: &Program) -> Fingerprint {
+    let cfg = build_cfg(program);
+    let code: Vec<u8> = cfg
+        .blocks
+        .iter()
+        .zip(cfg.reachable())
+        .filter(|(_, reachable)| *reachable)
+        .flat_map(|(block, _)| normalize(&block.opcodes))
+        .collect();
+    let ngrams: BTreeSet<u64> = match code.len() {
+        0 => BTreeSet::new(),
+        n if n < NGRAM => BTreeSet::from([fnv(&code)]),
+        _ => code.windows(NGRAM).map(fnv).collect(),
+    };
+    let mut minhash = [u64::MAX; PERMUTATIONS];
+    let mut weights = [0i64; 64];
+    for ngram in &ngrams {
+        for (i, min) in minhash.iter_mut().enumerate() {
+            *min = (*min).min(mix(ngram ^ (i as u64).wrapping_mul(0x9e3779b97f4a7c15)));
+        }
+        for (bit, weight) in weights.iter_mut().enumerate() {
+            *weight += match (ngram >> bit) & 1 {
+                1 => 1,
+                _ => -1,
+            };
+        }
+    }
+    let simhash = weights
+        .iter()
+        .enumerate()
+        .filter(|(_, weight)| **weight > 0)
+        .fold(0, |hash, (bit, _)| hash | 1 << bit);
+    let functions = function_bodies(program)
+        .into_iter()
+        .map(|(selector, body)| (selector, fnv(&normalize(&body))))
+        .collect();
+    Fingerprint {
+        functions,
+        minhash,
+        simhash,
+    }
+}
+
+/// Scores the similarity of two programs from 0 to 1.
+pub fn program_similarity(a: &Program, b: &Program) -> f64 {
+    fingerprint(a).similarity(&fingerprint(b))
+}
+
+/// Maps opcodes to their bytes, with every PUSH as `PUSH1`.
+fn normalize(opcodes: &[Opcode]) -> Vec<u8> {
+    opcodes
+        .iter()
+        .map(|op| match op.is_push() {
+            true => 0x60,
+            false => encode_opcode(op),
+        })
+        .collect()
+}
+
+/// The 64-bit FNV-1a hash, which unlike `std`'s hashers is stable across
+/// releases.
+fn fnv(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf29ce484222325, |hash, byte| {
+        (hash ^ *byte as u64).wrapping_mul(0x100000001b3)
+    })
+}
+
+/// The SplitMix64 finalizer, deriving the MinHash permutations.
+fn mix(mut x: u64) -> u64 {
+    x = (x ^ (x >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+    x = (x ^ (x >> 27)).wrapping_mul(0x94d049bb133111eb);
+    x ^ (x >> 31)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse::parse_program;
+    use alloc::{format, string::String};
+
+    fn dispatcher(functions: &[(u32, &str)]) -> Program {
+        let mut text = String::from("PUSH0 CALLDATALOAD PUSH1 0xe0 SHR\n");
+        for (selector, _) in functions {
+            text.push_str(&format!(
+                "DUP1 PUSH4 {:#010x} EQ PUSH @f{:x} JUMPI\n",
+                selector, selector
+            ));
+        }
+        text.push_str("PUSH0 DUP1 REVERT\n");
+        for (selector, body) in functions {
+            text.push_str(&format!("f{:x}: JUMPDEST {}\n", selector, body));
+        }
+        parse_program(&text).unwrap()
+    }
+
+    #[test]
+    fn identical() {
+        let a = dispatcher(&[(0x11111111, "PUSH1 0x01 PUSH0 SSTORE STOP")]);
+        // Other constants, wider pushes and unreachable metadata.
+        let b = dispatcher(&[(0x11111111, "PUSH2 0x1234 PUSH0 SSTORE STOP INVALID ADD")]);
+        let (a, b) = (fingerprint(&a), fingerprint(&b));
+        assert_eq!(a.minhash, b.minhash);
+        assert_eq!(a.simhash_distance(&b), 0);
+        assert_eq!(a.minhash_similarity(&b), 1.0);
+        assert_eq!(a.similarity(&b), 1.0);
+    }
+
+    #[test]
+    fn unrelated() {
+        let a = dispatcher(&[
+            (0x11111111, "PUSH1 0x01 PUSH0 SSTORE STOP"),
+            (
+                0x22222222,
+                "CALLER PUSH0 SLOAD EQ PUSH0 MSTORE PUSH1 0x20 PUSH0 RETURN",
+            ),
+        ]);
+        let b = parse_program(
+            "PUSH1 0x04 CALLDATALOAD l: JUMPDEST DUP1 ISZERO PUSH @e JUMPI \
+             PUSH1 0x01 SWAP1 SUB PUSH @l JUMP e: JUMPDEST POP GAS BALANCE \
+             TIMESTAMP MUL LOG1 SELFDESTRUCT",
+        )
+        .unwrap();
+        let (a, b) = (fingerprint(&a), fingerprint(&b));
+        assert_eq!(a.minhash_similarity(&b), 0.0);
+        assert!(a.simhash_distance(&b) > 16);
+        assert!(a.similarity(&b) < 0.5);
+        assert_eq!(a.function_similarity(&b), Some(0.0));
+        assert_eq!(b.function_similarity(&b), None);
+    }
+
+    #[test]
+    fn functions() {
+        let a = dispatcher(&[(0x11111111, "STOP"), (0x22222222, "CALLER POP STOP")]);
+        let b = dispatcher(&[(0x11111111, "STOP"), (0x33333333, "ORIGIN POP STOP")]);
+        let similarity = fingerprint(&a).function_similarity(&fingerprint(&b));
+        assert_eq!(similarity, Some(1.0 / 3.0));
+        assert!(program_similarity(&a, &b) < 1.0);
+        assert_eq!(program_similarity(&a, &a), 1.0);
+    }
+}
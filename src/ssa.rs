@@ -0,0 +1,668 @@
+// This is free and unencumbered software released into the public domain.
+
+use alloc::{
+    collections::{BTreeMap, BTreeSet},
+    format,
+    string::{String, ToString},
+    vec::Vec,
+};
+use core::fmt;
+use ethnum::u256;
+
+use crate::{
+    cfg::{build_cfg, Cfg},
+    error::SsaError,
+    lift::Value,
+    opcode::Opcode,
+    program::Program,
+};
+
+#[derive(Clone, Copy, Debug, Hash, PartialEq, Eq, PartialOrd, Ord)]
+pub enum SsaValue {
+    Const(u256),
+    /// The code address of the block starting at this PC.
+    Block(usize),
+    Var(usize),
+}
+
+impl fmt::Display for SsaValue {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SsaValue::Const(value) => write!(f, "{:#x}", value),
+            SsaValue::Block(pc) => write!(f, "block_{:#x}", pc),
+            SsaValue::Var(n) => write!(f, "v{}", n),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Hash, PartialEq, Eq)]
+pub struct SsaStatement {
+    pub pc: usize,
+    pub result: Option<usize>,
+    pub opcode: Opcode,
+    /// The operands, topmost stack item first.
+    pub args: Vec<SsaValue>,
+}
+
+impl fmt::Display for SsaStatement {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if let Some(result) = self.result {
+            write!(f, "{} = ", SsaValue::Var(result))?;
+        }
+        write!(f, "{}", self.opcode)?;
+        if !self.args.is_empty() {
+            write!(f, "({})", join(self.args.iter()))?;
+        }
+        Ok(())
+    }
+}
+
+/// A phi node defining the entry stack item at `slot`.
+#[derive(Clone, Copy, Debug, Hash, PartialEq, Eq)]
+pub struct Phi {
+    pub result: usize,
+    pub slot: usize,
+}
+
+#[derive(Clone, Debug, Hash, PartialEq, Eq)]
+pub struct SsaBlock {
+    pub start: usize,
+    pub jumpdest: bool,
+    /// The entry stack items the block and its successors read, topmost first.
+    pub entry: Vec<SsaValue>,
+    pub phis: Vec<Phi>,
+    /// How many entry stack items the block consumes.
+    pub inputs: usize,
+    pub statements: Vec<SsaStatement>,
+    /// The items replacing the consumed inputs on exit, topmost first.
+    pub outputs: Vec<SsaValue>,
+    pub predecessors: Vec<usize>,
+    pub successors: Vec<usize>,
+}
+
+impl SsaBlock {
+    pub fn exit(&self, slot: usize) -> Option<SsaValue> {
+        match self.outputs.get(slot) {
+            Some(value) => Some(*value),
+            None => self
+                .entry
+                .get(slot - self.outputs.len() + self.inputs)
+                .copied(),
+        }
+    }
+
+    pub fn terminator(&self) -> Option<&SsaStatement> {
+        self.statements
+            .last()
+            .filter(|stmt| stmt.opcode.is_control())
+    }
+
+    fn values_mut(&mut self) -> impl Iterator<Item = &mut SsaValue> {
+        self.entry
+            .iter_mut()
+            .chain(self.statements.iter_mut().flat_map(|stmt| &mut stmt.args))
+            .chain(self.outputs.iter_mut())
+    }
+}
+
+/// Where an SSA variable is defined or used.
+#[derive(Clone, Copy, Debug, Hash, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Site {
+    /// The phi node for this entry stack slot of the block at this PC.
+    Phi(usize, usize),
+    /// The statement at this index in the block at this PC.
+    Statement(usize, usize),
+}
+
+#[derive(Clone, Debug, Default, Hash, PartialEq, Eq)]
+pub struct SsaProgram {
+    pub blocks: Vec<SsaBlock>,
+}
+
+pub fn build_ssa(program: &Program) -> SsaProgram {
+    let cfg = build_cfg(program);
+    let reachable = cfg.reachable();
+    let mut next_var = cfg
+        .lifted
+        .iter()
+        .flat_map(|block| block.statements.iter().filter_map(|stmt| stmt.result))
+        .max()
+        .map_or(0, |var| var + 1);
+    let mut blocks = Vec::new();
+    for b in (0..cfg.blocks.len()).filter(|&b| reachable[b]) {
+        let lifted = &cfg.lifted[b];
+        let entry: Vec<SsaValue> = (next_var..next_var + cfg.depths[b])
+            .map(SsaValue::Var)
+            .collect();
+        let phis = (0..entry.len())
+            .map(|slot| Phi {
+                result: next_var + slot,
+                slot,
+            })
+            .collect();
+        next_var += entry.len();
+        let convert = |value: &Value| match *value {
+            Value::Const(value) => SsaValue::Const(value),
+            Value::Input(slot) => entry[slot],
+            Value::Var(n) => SsaValue::Var(n),
+        };
+        let statements = lifted
+            .statements
+            .iter()
+            .map(|stmt| SsaStatement {
+                pc: stmt.pc,
+                result: stmt.result,
+                opcode: stmt.opcode.clone(),
+                args: stmt.args.iter().map(convert).collect(),
+            })
+            .collect();
+        let outputs = lifted.outputs.iter().map(convert).collect();
+        blocks.push(SsaBlock {
+            start: cfg.blocks[b].start,
+            jumpdest: cfg.blocks[b].is_jumpdest(),
+            entry,
+            phis,
+            inputs: lifted.inputs,
+            statements,
+            outputs,
+            predecessors: block_starts(&cfg, cfg.predecessors[b].iter().filter(|&&p| reachable[p])),
+            successors: block_starts(&cfg, cfg.successors[b].iter()),
+        });
+    }
+    let mut result = SsaProgram { blocks };
+    result.remove_trivial_phis();
+    result.label_jump_targets();
+    result
+}
+
+impl SsaProgram {
+    pub fn block(&self, start: usize) -> Option<&SsaBlock> {
+        self.index_of(start).map(|index| &self.blocks[index])
+    }
+
+    pub fn phi_args(&self, block: &SsaBlock, phi: &Phi) -> Vec<(usize, SsaValue)> {
+        block
+            .predecessors
+            .iter()
+            .filter_map(|&pred| {
+                let value = self.block(pred)?.exit(phi.slot)?;
+                Some((pred, value))
+            })
+            .collect()
+    }
+
+    pub fn definitions(&self) -> BTreeMap<usize, Site> {
+        let mut result = BTreeMap::new();
+        for block in &self.blocks {
+            for phi in &block.phis {
+                result.insert(phi.result, Site::Phi(block.start, phi.slot));
+            }
+            for (index, stmt) in block.statements.iter().enumerate() {
+                if let Some(var) = stmt.result {
+                    result.insert(var, Site::Statement(block.start, index));
+                }
+            }
+        }
+        result
+    }
+
+    pub fn uses(&self) -> BTreeMap<usize, Vec<Site>> {
+        let mut result: BTreeMap<usize, Vec<Site>> = BTreeMap::new();
+        for block in &self.blocks {
+            for phi in &block.phis {
+                for (_, value) in self.phi_args(block, phi) {
+                    if let SsaValue::Var(var) = value {
+                        result
+                            .entry(var)
+                            .or_default()
+                            .push(Site::Phi(block.start, phi.slot));
+                    }
+                }
+            }
+            for (index, stmt) in block.statements.iter().enumerate() {
+                for arg in &stmt.args {
+                    if let SsaValue::Var(var) = arg {
+                        result
+                            .entry(*var)
+                            .or_default()
+                            .push(Site::Statement(block.start, index));
+                    }
+                }
+            }
+        }
+        result
+    }
+
+    /// Lowers the SSA form back into stack code, relocating jump targets.
+    ///
+    /// Only jump targets are relocated, and unreachable code such as data
+    /// and metadata is dropped, so programs that read their own code with
+    /// `CODECOPY` or `CODESIZE` are rejected. Values computed from `PC` are
+    /// folded into constants when lifted, and are not relocated either.
+    pub fn to_program(&self) -> Result<Program, SsaError> {
+        let definitions = self.definitions();
+        let mut code = Vec::new();
+        for block in &self.blocks {
+            let reads_code = block
+                .statements
+                .iter()
+                .find(|stmt| matches!(stmt.opcode, Opcode::CODECOPY | Opcode::CODESIZE));
+            if let Some(stmt) = reads_code {
+                return Err(SsaError::CodeReference(stmt.pc));
+            }
+            if let Some(stmt) = block.terminator().filter(|stmt| stmt.opcode.is_jump()) {
+                if let SsaValue::Var(var) = stmt.args[0] {
+                    if let Some(Site::Statement(_, _)) = definitions.get(&var) {
+                        return Err(SsaError::UnresolvedJump(stmt.pc));
+                    }
+                }
+            }
+            code.push((block.start, lower_block(block)?));
+        }
+        let labels = code
+            .iter()
+            .flat_map(|(_, items)| items)
+            .filter(|item| matches!(item, Item::Label(_)));
+        let (labels, ops) = (
+            labels.count(),
+            code.iter().flat_map(|(_, items)| items).count(),
+        );
+        let mut width = 1;
+        let mut offsets = BTreeMap::new();
+        loop {
+            offsets.clear();
+            let mut pc = 0;
+            for (start, items) in &code {
+                offsets.insert(*start, pc);
+                pc += items.iter().map(|item| item.size(width)).sum::<usize>();
+            }
+            if labels == 0 || (pc as u128) < 1 << (8 * width) {
+                break;
+            }
+            width += 1;
+        }
+        let mut opcodes = Vec::with_capacity(ops);
+        for item in code.into_iter().flat_map(|(_, items)| items) {
+            opcodes.push(match item {
+                Item::Op(op) => op,
+                Item::Label(start) => {
+                    Opcode::push_sized(width as u8, u256::from(offsets[&start] as u64)).unwrap()
+                }
+            });
+        }
+        Ok(Program(opcodes))
+    }
+
+    fn index_of(&self, start: usize) -> Option<usize> {
+        self.blocks
+            .binary_search_by_key(&start, |block| block.start)
+            .ok()
+    }
+
+    /// Replaces phi nodes whose arguments are all the same value (besides
+    /// the phi itself) by that value.
+    fn remove_trivial_phis(&mut self) {
+        let mut replaced: BTreeMap<usize, SsaValue> = BTreeMap::new();
+        let resolve = |replaced: &BTreeMap<usize, SsaValue>, mut value: SsaValue| {
+            while let SsaValue::Var(var) = value {
+                match replaced.get(&var) {
+                    Some(next) => value = *next,
+                    None => break,
+                }
+            }
+            value
+        };
+        let mut changed = true;
+        while changed {
+            changed = false;
+            for block in &self.blocks {
+                for phi in &block.phis {
+                    if replaced.contains_key(&phi.result) {
+                        continue;
+                    }
+                    let args: BTreeSet<SsaValue> = self
+                        .phi_args(block, phi)
+                        .into_iter()
+                        .map(|(_, value)| resolve(&replaced, value))
+                        .filter(|value| *value != SsaValue::Var(phi.result))
+                        .collect();
+                    if args.len() == 1 {
+                        replaced.insert(phi.result, *args.first().unwrap());
+                        changed = true;
+                    }
+                }
+            }
+        }
+        for block in &mut self.blocks {
+            block.phis.retain(|phi| !replaced.contains_key(&phi.result));
+            for value in block.values_mut() {
+                *value = resolve(&replaced, *value);
+            }
+        }
+    }
+
+    /// Turns constants that flow into jump targets into block labels, so that
+    /// lowering can relocate them.
+    fn label_jump_targets(&mut self) {
+        let mut addresses = BTreeSet::new();
+        let mut pending = Vec::new();
+        for b in 0..self.blocks.len() {
+            let target = match self.blocks[b].terminator() {
+                Some(stmt) if stmt.opcode.is_jump() => stmt.args[0],
+                _ => continue,
+            };
+            match target {
+                SsaValue::Const(pc) => {
+                    if let Some(label) = self.label(pc) {
+                        let last = self.blocks[b].statements.len() - 1;
+                        self.blocks[b].statements[last].args[0] = label;
+                    }
+                }
+                SsaValue::Var(var) => {
+                    addresses.insert(var);
+                }
+                SsaValue::Block(_) => {}
+            }
+        }
+        let phis: BTreeMap<usize, (usize, usize)> = self
+            .blocks
+            .iter()
+            .enumerate()
+            .flat_map(|(b, block)| {
+                block
+                    .phis
+                    .iter()
+                    .map(move |phi| (phi.result, (b, phi.slot)))
+            })
+            .collect();
+        pending.extend(addresses.iter().copied());
+        while let Some(var) = pending.pop() {
+            let Some(&(b, slot)) = phis.get(&var) else {
+                continue;
+            };
+            let mut exits: Vec<(usize, usize)> = self.blocks[b]
+                .predecessors
+                .iter()
+                .filter_map(|&pred| Some((self.index_of(pred)?, slot)))
+                .collect();
+            while let Some((p, slot)) = exits.pop() {
+                let block = &self.blocks[p];
+                let (value, in_entry) = match block.outputs.get(slot) {
+                    Some(value) => (*value, None),
+                    None => {
+                        let k = slot - block.outputs.len() + block.inputs;
+                        match block.entry.get(k) {
+                            Some(value) => (*value, Some(k)),
+                            None => continue,
+                        }
+                    }
+                };
+                match value {
+                    SsaValue::Var(var) => {
+                        if addresses.insert(var) {
+                            pending.push(var);
+                        }
+                    }
+                    SsaValue::Const(pc) => {
+                        let Some(label) = self.label(pc) else {
+                            continue;
+                        };
+                        let block = &mut self.blocks[p];
+                        match in_entry {
+                            None => block.outputs[slot] = label,
+                            Some(k) => {
+                                block.entry[k] = label;
+                                let preds = block.predecessors.clone();
+                                exits.extend(
+                                    preds
+                                        .iter()
+                                        .filter_map(|&pred| Some((self.index_of(pred)?, k))),
+                                );
+                            }
+                        }
+                    }
+                    SsaValue::Block(_) => {}
+                }
+            }
+        }
+    }
+
+    fn label(&self, pc: u256) -> Option<SsaValue> {
+        let pc = usize::try_from(pc).ok()?;
+        self.block(pc)
+            .filter(|block| block.jumpdest)
+            .map(|block| SsaValue::Block(block.start))
+    }
+}
+
+impl fmt::Display for SsaProgram {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for block in &self.blocks {
+            write!(f, "block_{:#x}:", block.start)?;
+            if !block.predecessors.is_empty() {
+                let preds = block.predecessors.iter().map(|&pc| SsaValue::Block(pc));
+                write!(f, " // preds: {}", join(preds.collect::<Vec<_>>().iter()))?;
+            }
+            writeln!(f)?;
+            for phi in &block.phis {
+                let args: Vec<String> = self
+                    .phi_args(block, phi)
+                    .iter()
+                    .map(|(pred, value)| format!("{}: {}", SsaValue::Block(*pred), value))
+                    .collect();
+                writeln!(f, "    v{} = phi({})", phi.result, args.join(", "))?;
+            }
+            for stmt in &block.statements {
+                writeln!(f, "    {}", stmt)?;
+            }
+            if !block.outputs.is_empty() {
+                writeln!(f, "    -> ({})", join(block.outputs.iter()))?;
+            }
+        }
+        Ok(())
+    }
+}
+
+enum Item {
+    Op(Opcode),
+    Label(usize),
+}
+
+impl Item {
+    fn size(&self, width: usize) -> usize {
+        match self {
+            Item::Op(op) => op.size(),
+            Item::Label(_) => 1 + width,
+        }
+    }
+}
+
+struct Lowering<'a> {
+    block: &'a SsaBlock,
+    /// The modelled stack, topmost item last.
+    stack: Vec<SsaValue>,
+    items: Vec<Item>,
+}
+
+impl Lowering<'_> {
+    fn push(&mut self, value: SsaValue) -> Result<(), SsaError> {
+        let item = match value {
+            SsaValue::Const(value) => Item::Op(Opcode::push(value)),
+            SsaValue::Block(start) => Item::Label(start),
+            SsaValue::Var(_) => {
+                let depth = self
+                    .stack
+                    .iter()
+                    .rev()
+                    .position(|item| *item == value)
+                    .map(|index| index + 1)
+                    .filter(|&depth| depth <= 16)
+                    .ok_or(SsaError::StackTooDeep(self.block.start))?;
+                Item::Op(Opcode::DUP(depth as u8))
+            }
+        };
+        self.items.push(item);
+        self.stack.push(value);
+        Ok(())
+    }
+
+    fn statement(&mut self, stmt: &SsaStatement) -> Result<(), SsaError> {
+        for arg in stmt.args.iter().rev() {
+            self.push(*arg)?;
+        }
+        self.items.push(Item::Op(stmt.opcode.clone()));
+        self.stack.truncate(self.stack.len() - stmt.args.len());
+        self.stack.extend(stmt.result.map(SsaValue::Var));
+        Ok(())
+    }
+
+    /// Leaves `values` (topmost first) on top of the items the block passes
+    /// through untouched, discarding everything else.
+    fn arrange(&mut self, values: &[SsaValue]) -> Result<(), SsaError> {
+        let kept = self.block.entry.len() - self.block.inputs;
+        let junk = self.stack.len() - kept;
+        let count = values.len();
+        let rotation = if count > 0 { junk % count } else { 0 };
+        for i in (0..count).rev() {
+            self.push(values[(i + count - rotation) % count])?;
+        }
+        if junk > 0 && count > 16 {
+            return Err(SsaError::StackTooDeep(self.block.start));
+        }
+        for _ in 0..junk {
+            if count > 0 {
+                self.items.push(Item::Op(Opcode::SWAP(count as u8)));
+            }
+            self.items.push(Item::Op(Opcode::POP));
+        }
+        Ok(())
+    }
+}
+
+fn lower_block(block: &SsaBlock) -> Result<Vec<Item>, SsaError> {
+    let mut lowering = Lowering {
+        block,
+        stack: block.entry.iter().rev().copied().collect(),
+        items: Vec::new(),
+    };
+    if block.jumpdest {
+        lowering.items.push(Item::Op(Opcode::JUMPDEST));
+    }
+    let (body, terminator) = match block.terminator() {
+        Some(stmt) => (&block.statements[..block.statements.len() - 1], Some(stmt)),
+        None => (&block.statements[..], None),
+    };
+    for stmt in body {
+        lowering.statement(stmt)?;
+    }
+    match terminator {
+        Some(stmt) if stmt.opcode.is_jump() => {
+            let values: Vec<SsaValue> = stmt.args.iter().chain(&block.outputs).copied().collect();
+            lowering.arrange(&values)?;
+            lowering.items.push(Item::Op(stmt.opcode.clone()));
+        }
+        Some(stmt) => lowering.statement(stmt)?,
+        None => lowering.arrange(&block.outputs)?,
+    }
+    Ok(lowering.items)
+}
+
+fn block_starts<'a>(cfg: &Cfg, blocks: impl Iterator<Item = &'a usize>) -> Vec<usize> {
+    blocks.map(|&b| cfg.blocks[b].start).collect()
+}
+
+fn join<'a>(values: impl Iterator<Item = &'a SsaValue>) -> String {
+    values
+        .map(SsaValue::to_string)
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        decode::decode_program, dispatch::extract_functions, encode::encode_program,
+        parse::parse_program,
+    };
+    use alloc::vec;
+
+    #[test]
+    fn phi_at_join() {
+        let program = parse_program(
+            "PUSH0 CALLDATALOAD PUSH @a JUMPI
+            PUSH1 0x01 PUSH @join JUMP
+            a: JUMPDEST PUSH1 0x02
+            join: JUMPDEST PUSH0 SSTORE STOP",
+        )
+        .unwrap();
+        let ssa = build_ssa(&program);
+        let join = ssa.block(0xd).unwrap();
+        assert_eq!(join.predecessors, vec![0x5, 0xa]);
+        let [phi] = join.phis.as_slice() else {
+            panic!("expected one phi:\n{}", ssa);
+        };
+        assert_eq!(
+            ssa.phi_args(join, phi),
+            vec![
+                (0x5, SsaValue::Const(u256::ONE)),
+                (0xa, SsaValue::Const(u256::new(2)))
+            ]
+        );
+        // The store is renamed to read the phi.
+        assert_eq!(
+            join.statements[0].args,
+            vec![SsaValue::Const(u256::ZERO), SsaValue::Var(phi.result)]
+        );
+        // The jump target is a label for relocation.
+        let jump = ssa.block(0x5).unwrap().terminator().unwrap();
+        assert_eq!(jump.args[0], SsaValue::Block(0xd));
+    }
+
+    #[test]
+    fn trivial_phi_removed() {
+        let program = parse_program(
+            "CALLER PUSH0 CALLDATALOAD PUSH @a JUMPI
+            PUSH @join JUMP
+            a: JUMPDEST
+            join: JUMPDEST PUSH0 SSTORE STOP",
+        )
+        .unwrap();
+        let ssa = build_ssa(&program);
+        let join = ssa.blocks.last().unwrap();
+        assert!(join.phis.is_empty(), "{}", ssa);
+        let caller = ssa.blocks[0].statements[0].result.unwrap();
+        assert_eq!(join.statements[0].args[1], SsaValue::Var(caller));
+    }
+
+    #[test]
+    fn dispatcher_round_trip() {
+        // A solc runtime dispatcher with a single `f()` function.
+        let bytecode = hex::decode(concat!(
+            "6080604052348015600f57600080fd5b506004361060285760003560e01c8063",
+            "26121ff014602d575b600080fd5b60336035565b005b56"
+        ))
+        .unwrap();
+        let program = decode_program(&bytecode).unwrap();
+        let lowered = build_ssa(&program).to_program().unwrap();
+        let lowered = decode_program(&encode_program(lowered)).unwrap();
+        let selectors = |program: &Program| -> Vec<(u32, bool)> {
+            extract_functions(program)
+                .iter()
+                .map(|function| (function.selector, function.payable))
+                .collect()
+        };
+        assert_eq!(selectors(&lowered), vec![(0x26121ff0, false)]);
+        // Lowering its own output gives the same code again.
+        assert_eq!(build_ssa(&lowered).to_program(), Ok(lowered));
+    }
+
+    #[test]
+    fn code_reference() {
+        let program = parse_program("PUSH1 0x20 PUSH1 0x10 PUSH0 CODECOPY STOP").unwrap();
+        assert_eq!(
+            build_ssa(&program).to_program(),
+            Err(SsaError::CodeReference(5))
+        );
+    }
+}
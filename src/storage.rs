@@ -0,0 +1,300 @@
+// This is free and unencumbered software released into the public domain.
+
+use alloc::{
+    collections::{BTreeMap, BTreeSet},
+    vec,
+    vec::Vec,
+};
+use ethnum::u256;
+
+use crate::{
+    dispatch::entry_states,
+    opcode::Opcode,
+    program::Program,
+    symbolic::{explore_symbolic_with, Expr, SymbolicLimits},
+};
+
+#[derive(Clone, Copy, Debug, Hash, PartialEq, Eq, PartialOrd, Ord)]
+pub enum SlotKind {
+    /// A variable at a fixed slot.
+    Constant(u256),
+    /// The entries of a mapping declared at `slot`, at slots computed as
+    /// `keccak256(key . slot)`, with `depth` levels of nested mappings.
+    Mapping { slot: u256, depth: usize },
+    /// The elements of a dynamic array, or the contents of a long `bytes`
+    /// or `string`, declared at `slot`, starting at `keccak256(slot)`.
+    Array { slot: u256 },
+    /// A slot computed in some other way.
+    Unknown,
+}
+
+/// An `SLOAD` or `SSTORE` instruction and the slot it accesses.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct StorageAccess {
+    pub pc: usize,
+    pub opcode: Opcode,
+    pub slot: Expr,
+    pub kind: SlotKind,
+}
+
+/// A variable in the recovered storage layout.
+#[derive(Clone, Copy, Debug, Hash, PartialEq, Eq, PartialOrd, Ord)]
+pub struct StorageVariable {
+    pub kind: SlotKind,
+    /// The offset of the variable within its slot, in bytes from the least
+    /// significant end, for variables packed together.
+    pub offset: usize,
+    pub size: usize,
+    pub read: bool,
+    pub written: bool,
+}
+
+/// Returns the slot accessed by each storage instruction, one entry per
+/// distinct slot expression.
+pub fn storage_accesses(program: &Program) -> Vec<StorageAccess> {
+    analyze(program).0
+}
+
+/// Recovers an approximate storage layout, with a variable for each slot
+/// (or kind of computed slot) accessed, split into the fields packed into
+/// it as seen from the masks applied to it.
+pub fn storage_layout(program: &Program) -> Vec<StorageVariable> {
+    let (accesses, fields) = analyze(program);
+    let mut result: BTreeMap<(SlotKind, usize, usize), StorageVariable> = BTreeMap::new();
+    let kinds: BTreeSet<SlotKind> = accesses.iter().map(|access| access.kind).collect();
+    for kind in kinds {
+        let read = accesses
+            .iter()
+            .any(|access| access.kind == kind && access.opcode == Opcode::SLOAD);
+        let written = accesses
+            .iter()
+            .any(|access| access.kind == kind && access.opcode == Opcode::SSTORE);
+        let packed = fields.get(&kind);
+        let fields = match packed {
+            Some(fields) => fields.iter().copied().collect(),
+            None => vec![(0, 32)],
+        };
+        for (offset, size) in fields {
+            result.insert(
+                (kind, offset, size),
+                StorageVariable {
+                    kind,
+                    offset,
+                    size,
+                    read,
+                    written,
+                },
+            );
+        }
+    }
+    result.into_values().collect()
+}
+
+type Fields = BTreeMap<SlotKind, BTreeSet<(usize, usize)>>;
+
+fn analyze(program: &Program) -> (Vec<StorageAccess>, Fields) {
+    let limits = SymbolicLimits {
+        max_steps: 10_000,
+        max_paths: 256,
+    };
+    let mut accesses: BTreeSet<(usize, Opcode, Expr)> = BTreeSet::new();
+    let mut fields = Fields::new();
+    for initial in entry_states(program) {
+        explore_symbolic_with(program, initial, limits, |state, op| {
+            let top = |n: usize| {
+                state
+                    .stack
+                    .len()
+                    .checked_sub(n + 1)
+                    .map(|i| &state.stack[i])
+            };
+            if op.is_storage() {
+                if let Some(slot) = top(0) {
+                    accesses.insert((state.pc, op.clone(), slot.clone()));
+                }
+            }
+            if *op == Opcode::AND {
+                if let (Some(a), Some(b)) = (top(0), top(1)) {
+                    if let Some((kind, field)) = field(a, b).or_else(|| field(b, a)) {
+                        fields.entry(kind).or_default().insert(field);
+                    }
+                }
+            }
+        });
+    }
+    let accesses = accesses
+        .into_iter()
+        .map(|(pc, opcode, slot)| StorageAccess {
+            pc,
+            kind: classify_slot(&slot),
+            opcode,
+            slot,
+        })
+        .collect();
+    (accesses, fields)
+}
+
+/// Classifies a slot expression.
+pub fn classify_slot(slot: &Expr) -> SlotKind {
+    match base(slot) {
+        Expr::Const(slot) => SlotKind::Constant(*slot),
+        Expr::Sha3(words) => match words.as_slice() {
+            [Expr::Const(slot)] => SlotKind::Array { slot: *slot },
+            [_, inner] => match classify_slot(inner) {
+                SlotKind::Constant(slot) => SlotKind::Mapping { slot, depth: 1 },
+                SlotKind::Mapping { slot, depth } => SlotKind::Mapping {
+                    slot,
+                    depth: depth + 1,
+                },
+                _ => SlotKind::Unknown,
+            },
+            _ => SlotKind::Unknown,
+        },
+        _ => SlotKind::Unknown,
+    }
+}
+
+/// Strips the constant or index offsets added to a hashed slot, as for
+/// struct members and array elements.
+fn base(slot: &Expr) -> &Expr {
+    match slot {
+        Expr::Op(Opcode::ADD, args) => match (&args[0], &args[1]) {
+            (hash @ Expr::Sha3(_), _) | (_, hash @ Expr::Sha3(_)) => hash,
+            _ => slot,
+        },
+        _ => slot,
+    }
+}
+
+/// Returns the field of a storage word that an `AND` of `mask` and `value`
+/// extracts or clears, as an offset and size in bytes.
+fn field(mask: &Expr, value: &Expr) -> Option<(SlotKind, (usize, usize))> {
+    let mask = mask.constant()?;
+    let (value, shift) = match value {
+        Expr::Op(Opcode::SHR, args) => (&args[1], args[0].constant()?),
+        Expr::Op(Opcode::DIV, args) => {
+            let divisor = args[1].constant()?;
+            if divisor.count_ones() != 1 {
+                return None;
+            }
+            (&args[0], u256::from(divisor.trailing_zeros()))
+        }
+        _ => (value, u256::ZERO),
+    };
+    if shift >= 256 {
+        return None;
+    }
+    let slot = match value {
+        Expr::Op(Opcode::SLOAD, args) | Expr::Effect(_, Opcode::SLOAD, args) => &args[0],
+        _ => return None,
+    };
+    // An extracting mask is a run of ones, a clearing mask the inverse of
+    // a run of ones at the field's offset.
+    let (offset, bits) = match (run(mask), run(!mask)) {
+        (Some((offset, bits)), _) => (shift.as_u32() + offset, bits),
+        (None, Some(field)) if shift == 0 => field,
+        _ => return None,
+    };
+    if bits >= 256 || offset % 8 != 0 || bits % 8 != 0 {
+        return None;
+    }
+    Some((
+        classify_slot(slot),
+        (offset as usize / 8, bits as usize / 8),
+    ))
+}
+
+/// Returns the offset and length of a word's bits if they are a single run
+/// of ones.
+fn run(word: u256) -> Option<(u32, u32)> {
+    let offset = word.trailing_zeros();
+    let bits = word.count_ones();
+    (bits > 0 && (word >> offset).trailing_ones() == bits).then_some((offset, bits))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse::parse_program;
+
+    fn calldata(offset: u32) -> Expr {
+        Expr::Op(Opcode::CALLDATALOAD, vec![Expr::Const(u256::from(offset))])
+    }
+
+    fn variable(kind: SlotKind, offset: usize, size: usize, read: bool) -> StorageVariable {
+        StorageVariable {
+            kind,
+            offset,
+            size,
+            read,
+            written: !read,
+        }
+    }
+
+    #[test]
+    fn slots() {
+        let mapping = Expr::Sha3(vec![calldata(4), Expr::Const(u256::ONE)]);
+        assert_eq!(
+            classify_slot(&mapping),
+            SlotKind::Mapping {
+                slot: u256::ONE,
+                depth: 1
+            }
+        );
+        let nested = Expr::Sha3(vec![calldata(0x24), mapping.clone()]);
+        assert_eq!(
+            classify_slot(&nested),
+            SlotKind::Mapping {
+                slot: u256::ONE,
+                depth: 2
+            }
+        );
+        let member = Expr::apply(Opcode::ADD, vec![mapping, Expr::Const(u256::ONE)]);
+        assert!(matches!(classify_slot(&member), SlotKind::Mapping { .. }));
+        let array = Expr::Sha3(vec![Expr::Const(u256::new(3))]);
+        assert_eq!(
+            classify_slot(&array),
+            SlotKind::Array { slot: u256::new(3) }
+        );
+        assert_eq!(classify_slot(&calldata(4)), SlotKind::Unknown);
+    }
+
+    #[test]
+    fn layout() {
+        // A mapping, a nested mapping written, an array element, an address
+        // and a byte packed into slot 0, and a word written to slot 4.
+        let program = parse_program(
+            "PUSH 4 CALLDATALOAD PUSH 0 MSTORE PUSH 1 PUSH 0x20 MSTORE
+             PUSH 0x40 PUSH 0 SHA3 SLOAD POP
+             PUSH 4 CALLDATALOAD PUSH 0 MSTORE PUSH 2 PUSH 0x20 MSTORE PUSH 0x40 PUSH 0 SHA3
+             PUSH 0x24 CALLDATALOAD PUSH 0 MSTORE PUSH 0x20 MSTORE PUSH 0x40 PUSH 0 SHA3
+             CALLER SWAP1 SSTORE
+             PUSH 3 PUSH 0 MSTORE PUSH 0x20 PUSH 0 SHA3 PUSH 0x44 CALLDATALOAD ADD SLOAD POP
+             PUSH 0 SLOAD PUSH 0xa0 SHR PUSH 0xff AND POP
+             PUSH 0 SLOAD PUSH 0xffffffffffffffffffffffffffffffffffffffff AND POP
+             CALLVALUE PUSH 4 SSTORE STOP",
+        )
+        .unwrap();
+        let accesses = storage_accesses(&program);
+        assert_eq!(accesses.len(), 6);
+        assert_eq!(
+            accesses[0].slot,
+            Expr::Sha3(vec![calldata(4), Expr::Const(u256::ONE)])
+        );
+        let mapping = |slot: u32, depth| SlotKind::Mapping {
+            slot: u256::from(slot),
+            depth,
+        };
+        assert_eq!(
+            storage_layout(&program),
+            [
+                variable(SlotKind::Constant(u256::ZERO), 0, 20, true),
+                variable(SlotKind::Constant(u256::ZERO), 20, 1, true),
+                variable(SlotKind::Constant(u256::new(4)), 0, 32, false),
+                variable(mapping(1, 1), 0, 32, true),
+                variable(mapping(2, 2), 0, 32, false),
+                variable(SlotKind::Array { slot: u256::new(3) }, 0, 32, true),
+            ]
+        );
+    }
+}
@@ -0,0 +1,545 @@
+// This is free and unencumbered software released into the public domain.
+
+use alloc::{collections::BTreeMap, vec, vec::Vec};
+use core::fmt;
+use ethnum::u256;
+
+use crate::{eval::eval_opcode, opcode::Opcode, program::Program};
+
+#[derive(Clone, Debug, Hash, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Expr {
+    /// A concrete word.
+    Const(u256),
+    /// A pure function of its operands and the initial environment, e.g.
+    /// `ADD(..)`, `CALLDATALOAD(..)`, `CALLVALUE` or the initial `SLOAD(..)`.
+    Op(Opcode, Vec<Expr>),
+    /// The Keccak-256 hash of the concatenation of these words.
+    Sha3(Vec<Expr>),
+    /// The result of the instruction at this PC, which may differ between
+    /// executions even given the same operands, e.g. `CALL` or `GAS`.
+    Effect(usize, Opcode, Vec<Expr>),
+}
+
+impl Expr {
+    pub fn apply(opcode: Opcode, args: Vec<Expr>) -> Expr {
+        let values: Option<Vec<u256>> = args.iter().map(Expr::constant).collect();
+        match values.and_then(|values| eval_opcode(&opcode, &values)) {
+            Some(value) => Expr::Const(value),
+            None => Expr::Op(opcode, args),
+        }
+    }
+
+    pub fn args(&self) -> &[Expr] {
+        use Expr::*;
+        match self {
+            Const(_) => &[],
+            Op(_, args) | Sha3(args) | Effect(_, _, args) => args,
+        }
+    }
+
+    pub fn constant(&self) -> Option<u256> {
+        match self {
+            Expr::Const(value) => Some(*value),
+            _ => None,
+        }
+    }
+
+    pub fn contains(&self, predicate: &impl Fn(&Expr) -> bool) -> bool {
+        predicate(self) || self.args().iter().any(|arg| arg.contains(predicate))
+    }
+
+    pub fn is_const(&self) -> bool {
+        matches!(self, Expr::Const(_))
+    }
+
+    pub fn is_op(&self, opcode: &Opcode) -> bool {
+        matches!(self, Expr::Op(op, _) if op == opcode)
+    }
+
+    /// Returns a condition that is zero exactly when this one is nonzero,
+    /// which for `ISZERO(x)` is `x` itself, although not the same word.
+    pub fn negate(&self) -> Expr {
+        match self {
+            Expr::Op(Opcode::ISZERO, args) => args[0].clone(),
+            _ => Expr::apply(Opcode::ISZERO, vec![self.clone()]),
+        }
+    }
+
+    pub fn visit(&self, visitor: &mut impl FnMut(&Expr)) {
+        visitor(self);
+        for arg in self.args() {
+            arg.visit(visitor);
+        }
+    }
+}
+
+impl From<u256> for Expr {
+    fn from(value: u256) -> Self {
+        Expr::Const(value)
+    }
+}
+
+impl fmt::Display for Expr {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        use Expr::*;
+        let write_args = |f: &mut fmt::Formatter, args: &[Expr], sep: &str| {
+            for (i, arg) in args.iter().enumerate() {
+                if i > 0 {
+                    write!(f, "{}", sep)?;
+                }
+                write!(f, "{}", arg)?;
+            }
+            Ok(())
+        };
+        match self {
+            Const(value) => write!(f, "{:#x}", value),
+            Op(op, args) if args.is_empty() => write!(f, "{}", op),
+            Op(op, args) => {
+                write!(f, "{}(", op)?;
+                write_args(f, args, ", ")?;
+                write!(f, ")")
+            }
+            Sha3(args) => {
+                write!(f, "keccak256(")?;
+                write_args(f, args, " . ")?;
+                write!(f, ")")
+            }
+            Effect(pc, op, args) => {
+                write!(f, "{}@{:#x}(", op, pc)?;
+                write_args(f, args, ", ")?;
+                write!(f, ")")
+            }
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MemoryWrite {
+    pub offset: Expr,
+    pub size: Expr,
+    /// The written word or byte, or `None` for bulk copies whose contents
+    /// are unknown (`CALLDATACOPY`, return data, etc).
+    pub value: Option<Expr>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SideEffect {
+    pub pc: usize,
+    pub opcode: Opcode,
+    pub args: Vec<Expr>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Outcome {
+    Halt(Opcode),
+    StackUnderflow,
+    InvalidJump(Expr),
+    UnresolvedJump(Expr),
+    StepLimit,
+    PathLimit,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct SymbolicLimits {
+    pub max_steps: usize,
+    pub max_paths: usize,
+}
+
+impl Default for SymbolicLimits {
+    fn default() -> Self {
+        SymbolicLimits {
+            max_steps: 10_000,
+            max_paths: 1_024,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct SymbolicState {
+    pub pc: usize,
+    /// The stack, with the topmost item last.
+    pub stack: Vec<Expr>,
+    pub memory: Vec<MemoryWrite>,
+    pub storage: Vec<(Expr, Expr)>,
+    /// Conditions assumed to be nonzero along this path.
+    pub constraints: Vec<Expr>,
+    pub effects: Vec<SideEffect>,
+    pub steps: usize,
+    pub outcome: Option<Outcome>,
+}
+
+pub fn execute_symbolic(program: &Program, limits: SymbolicLimits) -> Vec<SymbolicState> {
+    explore_symbolic(program, SymbolicState::default(), limits)
+}
+
+/// Explores every path reachable from `initial` depth-first, returning the
+/// final state of each path.
+pub fn explore_symbolic(
+    program: &Program,
+    initial: SymbolicState,
+    limits: SymbolicLimits,
+) -> Vec<SymbolicState> {
+    explore_symbolic_with(program, initial, limits, |_, _| {})
+}
+
+/// Like `explore_symbolic`, but also calls `observe` with each state and
+/// the instruction it is about to execute.
+pub fn explore_symbolic_with(
+    program: &Program,
+    initial: SymbolicState,
+    limits: SymbolicLimits,
+    mut observe: impl FnMut(&SymbolicState, &Opcode),
+) -> Vec<SymbolicState> {
+    let code: BTreeMap<usize, &Opcode> = program.instructions().collect();
+    let mut result = Vec::new();
+    let mut pending = vec![initial];
+    let mut paths = 1;
+    while let Some(mut state) = pending.pop() {
+        while state.outcome.is_none() {
+            if let Some(op) = code.get(&state.pc) {
+                observe(&state, op);
+            }
+            if let Some(mut fork) = state.step(&code, limits) {
+                if paths < limits.max_paths {
+                    paths += 1;
+                    pending.push(fork);
+                } else {
+                    fork.outcome = Some(Outcome::PathLimit);
+                    result.push(fork);
+                }
+            }
+        }
+        result.push(state);
+    }
+    result
+}
+
+impl SymbolicState {
+    pub fn new(pc: usize, stack: Vec<Expr>) -> Self {
+        SymbolicState {
+            pc,
+            stack,
+            ..Default::default()
+        }
+    }
+
+    pub fn is_halted(&self) -> bool {
+        matches!(self.outcome, Some(Outcome::Halt(_)))
+    }
+
+    /// Executes one instruction, returning the other branch of a `JUMPI`
+    /// whose condition is symbolic.
+    pub fn step(
+        &mut self,
+        code: &BTreeMap<usize, &Opcode>,
+        limits: SymbolicLimits,
+    ) -> Option<SymbolicState> {
+        use Opcode::*;
+        if self.outcome.is_some() {
+            return None;
+        }
+        if self.steps >= limits.max_steps {
+            self.outcome = Some(Outcome::StepLimit);
+            return None;
+        }
+        self.steps += 1;
+        let op = match code.get(&self.pc) {
+            Some(op) => (*op).clone(),
+            None => {
+                self.outcome = Some(Outcome::Halt(STOP));
+                return None;
+            }
+        };
+        if self.stack.len() < op.stack_inputs() {
+            self.outcome = Some(Outcome::StackUnderflow);
+            return None;
+        }
+        let pc = self.pc;
+        self.pc += op.size();
+        let len = self.stack.len();
+        let args: Vec<Expr> = match op {
+            PUSH(push) => return self.push(Expr::Const(push.value())),
+            DUP(n) => return self.push(self.stack[len - n as usize].clone()),
+            SWAP(n) => {
+                self.stack.swap(len - 1, len - 1 - n as usize);
+                return None;
+            }
+            _ => (0..op.stack_inputs())
+                .map(|_| self.stack.pop().unwrap())
+                .collect(),
+        };
+        match op {
+            JUMPDEST | POP => {}
+            JUMP => self.jump(code, &args[0]),
+            JUMPI => return self.branch(code, &args[0], &args[1]),
+            STOP | RETURN | REVERT | INVALID | SELFDESTRUCT => {
+                self.effect(pc, &op, &args);
+                self.outcome = Some(Outcome::Halt(op));
+            }
+            MSTORE => self.memory.push(MemoryWrite {
+                offset: args[0].clone(),
+                size: Expr::Const(u256::new(32)),
+                value: Some(args[1].clone()),
+            }),
+            MSTORE8 => self.memory.push(MemoryWrite {
+                offset: args[0].clone(),
+                size: Expr::Const(u256::ONE),
+                value: Some(Expr::apply(
+                    AND,
+                    vec![args[1].clone(), u256::new(0xFF).into()],
+                )),
+            }),
+            CALLDATACOPY | CODECOPY | RETURNDATACOPY => self.clobber(&args[0], &args[2]),
+            EXTCODECOPY => self.clobber(&args[1], &args[3]),
+            SSTORE => {
+                self.effect(pc, &op, &args);
+                self.storage.push((args[0].clone(), args[1].clone()));
+            }
+            LOG(_) => self.effect(pc, &op, &args),
+            CALL | CALLCODE | DELEGATECALL | STATICCALL => {
+                self.effect(pc, &op, &args);
+                let n = args.len();
+                self.clobber(&args[n - 2], &args[n - 1]);
+                self.push(Expr::Effect(pc, op, args));
+            }
+            CREATE | CREATE2 => {
+                self.effect(pc, &op, &args);
+                self.push(Expr::Effect(pc, op, args));
+            }
+            GAS | MSIZE | RETURNDATASIZE => {
+                self.push(Expr::Effect(pc, op, args));
+            }
+            PC => {
+                self.push(Expr::Const(u256::from(pc as u64)));
+            }
+            MLOAD => {
+                let value = self.mload(&args[0]);
+                self.push(value.unwrap_or(Expr::Effect(pc, op, args)));
+            }
+            SLOAD => {
+                let value = self.sload(pc, &args[0]);
+                self.push(value);
+            }
+            SHA3 => {
+                let value = self.sha3(&args[0], &args[1]);
+                self.push(value.unwrap_or(Expr::Effect(pc, op, args)));
+            }
+            _ => {
+                self.push(Expr::apply(op, args));
+            }
+        }
+        None
+    }
+
+    fn push(&mut self, expr: Expr) -> Option<SymbolicState> {
+        self.stack.push(expr);
+        None
+    }
+
+    fn effect(&mut self, pc: usize, opcode: &Opcode, args: &[Expr]) {
+        self.effects.push(SideEffect {
+            pc,
+            opcode: opcode.clone(),
+            args: args.to_vec(),
+        });
+    }
+
+    fn jump(&mut self, code: &BTreeMap<usize, &Opcode>, dest: &Expr) {
+        let target = dest.constant().and_then(|d| usize::try_from(d).ok());
+        match target {
+            Some(pc) if code.get(&pc).is_some_and(|op| op.is_jumpdest()) => self.pc = pc,
+            Some(_) => self.outcome = Some(Outcome::InvalidJump(dest.clone())),
+            None => self.outcome = Some(Outcome::UnresolvedJump(dest.clone())),
+        }
+    }
+
+    fn branch(
+        &mut self,
+        code: &BTreeMap<usize, &Opcode>,
+        dest: &Expr,
+        cond: &Expr,
+    ) -> Option<SymbolicState> {
+        let cond = normalize(cond.clone());
+        let negated = normalize(cond.negate());
+        match cond.constant() {
+            Some(value) if value != u256::ZERO => self.jump(code, dest),
+            Some(_) => {}
+            None if self.constraints.contains(&cond) => self.jump(code, dest),
+            None if self.constraints.contains(&negated) => {}
+            None => {
+                let mut fork = self.clone();
+                fork.constraints.push(cond);
+                fork.jump(code, dest);
+                self.constraints.push(negated);
+                return Some(fork);
+            }
+        }
+        None
+    }
+
+    fn clobber(&mut self, offset: &Expr, size: &Expr) {
+        self.memory.push(MemoryWrite {
+            offset: offset.clone(),
+            size: size.clone(),
+            value: None,
+        });
+    }
+
+    /// Returns the word at `offset` if the writes to memory so far determine
+    /// it, treating memory that was never written as zero.
+    fn mload(&self, offset: &Expr) -> Option<Expr> {
+        let word = Expr::Const(u256::new(32));
+        for write in self.memory.iter().rev() {
+            if write.offset == *offset && write.size == word {
+                return write.value.clone();
+            }
+            if !disjoint(&write.offset, &write.size, offset, &word) {
+                return None;
+            }
+        }
+        Some(Expr::Const(u256::ZERO))
+    }
+
+    fn sload(&self, pc: usize, slot: &Expr) -> Expr {
+        for (key, value) in self.storage.iter().rev() {
+            if key == slot {
+                return value.clone();
+            }
+            if !(key.is_const() && slot.is_const()) {
+                return Expr::Effect(pc, Opcode::SLOAD, vec![slot.clone()]);
+            }
+        }
+        Expr::Op(Opcode::SLOAD, vec![slot.clone()])
+    }
+
+    fn sha3(&self, offset: &Expr, size: &Expr) -> Option<Expr> {
+        let (offset, size) = (offset.constant()?, size.constant()?);
+        if size % 32 != 0 || size > 32 * 16 {
+            return None;
+        }
+        let words = (0..size.as_u32() / 32)
+            .map(|i| self.mload(&Expr::Const(offset.wrapping_add(u256::from(i * 32)))))
+            .collect::<Option<Vec<Expr>>>()?;
+        Some(Expr::Sha3(words))
+    }
+}
+
+/// Simplifies a branch condition, of which only whether it is zero matters:
+/// `ISZERO(ISZERO(x))` is `x` as a condition, but not as a word.
+fn normalize(expr: Expr) -> Expr {
+    match expr {
+        Expr::Op(Opcode::ISZERO, args) if args[0].is_op(&Opcode::ISZERO) => {
+            normalize(args[0].args()[0].clone())
+        }
+        expr => expr,
+    }
+}
+
+fn disjoint(a_offset: &Expr, a_size: &Expr, b_offset: &Expr, b_size: &Expr) -> bool {
+    let values = (
+        a_offset.constant(),
+        a_size.constant(),
+        b_offset.constant(),
+        b_size.constant(),
+    );
+    match values {
+        (_, Some(u256::ZERO), _, _) | (_, _, _, Some(u256::ZERO)) => true,
+        (Some(a), Some(a_size), Some(b), Some(b_size)) => {
+            a.checked_add(a_size).is_some_and(|a_end| a_end <= b)
+                || b.checked_add(b_size).is_some_and(|b_end| b_end <= a)
+        }
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse::parse_program;
+
+    fn explore(input: &str) -> Vec<SymbolicState> {
+        execute_symbolic(&parse_program(input).unwrap(), SymbolicLimits::default())
+    }
+
+    fn callvalue() -> Expr {
+        Expr::Op(Opcode::CALLVALUE, vec![])
+    }
+
+    #[test]
+    fn two_way_branch() {
+        let states = explore("CALLVALUE PUSH @paid JUMPI PUSH 1 STOP paid: JUMPDEST PUSH 2 STOP");
+        assert_eq!(states.len(), 2);
+        let (unpaid, paid) = (&states[0], &states[1]);
+        assert_eq!(
+            unpaid.constraints,
+            [Expr::apply(Opcode::ISZERO, vec![callvalue()])]
+        );
+        assert_eq!(unpaid.stack, [Expr::Const(u256::ONE)]);
+        assert_eq!(paid.constraints, [callvalue()]);
+        assert_eq!(paid.stack, [Expr::Const(u256::new(2))]);
+        assert!(states.iter().all(SymbolicState::is_halted));
+    }
+
+    #[test]
+    fn correlated_branches() {
+        // The second branch on the same condition follows the first.
+        let states = explore(
+            "CALLVALUE PUSH @a JUMPI a: JUMPDEST CALLVALUE ISZERO PUSH @b JUMPI PUSH 1 STOP b: JUMPDEST STOP",
+        );
+        assert_eq!(states.len(), 2);
+        assert_eq!(states[0].stack, []);
+        assert_eq!(
+            states[0].constraints,
+            [Expr::apply(Opcode::ISZERO, vec![callvalue()])]
+        );
+        assert_eq!(states[1].stack, [Expr::Const(u256::ONE)]);
+        assert_eq!(states[1].constraints, [callvalue()]);
+    }
+
+    #[test]
+    fn double_negation() {
+        // ISZERO(ISZERO(x)) is only simplified as a branch condition.
+        let states = explore("CALLVALUE ISZERO ISZERO DUP1 PUSH @t JUMPI STOP t: JUMPDEST STOP");
+        let word = Expr::apply(
+            Opcode::ISZERO,
+            vec![Expr::apply(Opcode::ISZERO, vec![callvalue()])],
+        );
+        assert_eq!(states[1].constraints, [callvalue()]);
+        assert_eq!(states[1].stack[0], word);
+        assert_eq!(states[0].constraints, [word.negate()]);
+        assert_eq!(
+            word.negate(),
+            Expr::apply(Opcode::ISZERO, vec![callvalue()])
+        );
+    }
+
+    #[test]
+    fn memory_and_hashes() {
+        let states = explore("CALLVALUE PUSH 0 MSTORE PUSH 0 MLOAD PUSH 32 PUSH 0 SHA3 STOP");
+        assert_eq!(
+            states[0].stack,
+            [callvalue(), Expr::Sha3(vec![callvalue()])]
+        );
+    }
+
+    #[test]
+    fn limits() {
+        let limits = SymbolicLimits {
+            max_steps: 10,
+            max_paths: 1_024,
+        };
+        let program = parse_program("l: JUMPDEST PUSH @l JUMP").unwrap();
+        let states = execute_symbolic(&program, limits);
+        assert_eq!(states[0].outcome, Some(Outcome::StepLimit));
+        let states = explore("PUSH 1 JUMP");
+        assert_eq!(
+            states[0].outcome,
+            Some(Outcome::InvalidJump(u256::ONE.into()))
+        );
+        let states = explore("CALLVALUE JUMP");
+        assert_eq!(
+            states[0].outcome,
+            Some(Outcome::UnresolvedJump(callvalue()))
+        );
+    }
+}